@@ -11,6 +11,7 @@ pub fn main() {
         .unwrap();
     let mut pc: PerfCounter =
         PerfCounterBuilderLinux::from_intel_event_description(counter_description)
+            .expect("Can not encode the event")
             .exclude_idle()
             .exclude_kernel()
             .finish()
@@ -0,0 +1,24 @@
+extern crate perfcnt;
+
+use perfcnt::linux::HardwareEventType as Hardware;
+use perfcnt::linux::{PerfCounterBuilderLinux as Builder, PerfCounterGroupBuilder};
+
+pub fn main() {
+    let mut group = PerfCounterGroupBuilder::new(Builder::from_hardware_event(Hardware::CPUCycles))
+        .add_member(Builder::from_hardware_event(Hardware::Instructions))
+        .add_member(Builder::from_hardware_event(Hardware::CacheMisses))
+        .finish()
+        .expect("Could not create counter group");
+
+    group.start().expect("Can not start the group");
+    let mut sum: u64 = 0;
+    for i in 0..1_000_000 {
+        sum = sum.wrapping_add(i);
+    }
+    group.stop().expect("Can not stop the group");
+
+    println!("Sum: {}", sum);
+    for v in group.read().expect("Can not read group").values {
+        println!("config {:#x} (id {}): {}", v.event.config, v.id, v.value);
+    }
+}
@@ -1,3 +1,5 @@
+use perfcnt::linux::probe::{EventSupport, SystemProbe};
+use std::env;
 use x86::perfcnt::intel::{events, EventDescription};
 
 fn print_counter(id: &str, info: &EventDescription) {
@@ -20,7 +22,65 @@ fn print_counter(id: &str, info: &EventDescription) {
     println!(" ");
 }
 
+fn print_setting<T: std::fmt::Display>(name: &str, value: Option<T>) {
+    match value {
+        Some(v) => println!("{:<28} {}", name, v),
+        None => println!("{:<28} unknown", name),
+    }
+}
+
+fn print_events(title: &str, events: &[EventSupport]) {
+    let supported = events.iter().filter(|e| e.is_supported()).count();
+    println!(
+        "{} ({} of {} can be opened):",
+        title,
+        supported,
+        events.len()
+    );
+    for event in events {
+        match event.error {
+            None => println!("\t{:<32} yes", event.name),
+            Some(ref e) => println!("\t{:<32} no: {}", event.name, e),
+        }
+    }
+    println!();
+}
+
+fn print_probe() {
+    let probe = SystemProbe::new();
+
+    println!("What this machine allows:");
+    println!("----------------------------------------------------------");
+    print_setting("perf_event_paranoid", probe.permissions.paranoid);
+    print_setting(
+        "CAP_PERFMON/CAP_SYS_ADMIN",
+        probe
+            .permissions
+            .capabilities
+            .map(|_| probe.permissions.is_privileged()),
+    );
+    print_setting("perf_event_max_sample_rate", probe.max_sample_rate);
+    print_setting("perf_event_mlock_kb", probe.mlock_kb);
+    print_setting("rdpmc", probe.rdpmc);
+    println!("{:<28} {}", "kernel mode", probe.kernel_allowed());
+    println!("{:<28} {}", "system-wide", probe.system_wide_allowed());
+    println!();
+
+    print_events("Hardware events", &probe.hardware_events);
+    print_events("Cache events", &probe.cache_events);
+
+    println!("PMUs: {}", probe.pmus.join(", "));
+    for error in probe.pmu_errors.iter() {
+        println!("  skipped {}", error);
+    }
+}
+
 fn main() {
+    if env::args().any(|arg| arg == "--probe") {
+        print_probe();
+        return;
+    }
+
     println!("All supported events on this hardware:");
     println!("----------------------------------------------------------");
 
@@ -1,11 +1,29 @@
 use std::env;
-use std::fs::File;
-use std::io::prelude::*;
+use std::fs::{self, File};
+use std::io::{self, prelude::*};
 
+use perfcnt::linux::folded::{FoldedStacks, Weight};
 use perfcnt::linux::perf_file::PerfFile;
 
 fn main() {
-    for argument in env::args().skip(1) {
+    // --folded: print the callchains as folded stacks (for flame graphs) instead,
+    // --period: weight them by the sample period rather than the number of samples.
+    let folded = env::args().any(|arg| arg == "--folded");
+    let weight = if env::args().any(|arg| arg == "--period") {
+        Weight::Period
+    } else {
+        Weight::Samples
+    };
+
+    for argument in env::args().skip(1).filter(|arg| !arg.starts_with("--")) {
+        if folded {
+            let pf = PerfFile::new(fs::read(&argument).expect("File does not exist"));
+            FoldedStacks::from_perf_file(&pf, weight)
+                .write(&mut io::stdout())
+                .expect("Can not write folded stacks");
+            continue;
+        }
+
         println!("Parsed perf file: {}", argument);
         println!("----------------------------------------------------------");
 
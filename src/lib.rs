@@ -14,8 +14,12 @@
 //! ```
 
 pub mod linux;
+pub use crate::linux::error::PerfError;
 pub use crate::linux::PerfCounter;
 
+pub mod measure;
+pub use crate::measure::{Measure, MeasureGuard};
+
 use std::io;
 
 /// Abstract trait to control performance counters.
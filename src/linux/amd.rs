@@ -0,0 +1,233 @@
+//! Raw core events of AMD processors (family 17h and later, i.e., Zen).
+//!
+//! The event select is 12 bits wide, in `PERF_CTL` (and in the kernel's `cpu` PMU)
+//! bits 7:0 are stored in config[7:0] and bits 11:8 in config[35:32]:
+//!
+//! ```text
+//! event=config:0-7,32-35
+//! umask=config:8-15
+//! edge=config:18
+//! inv=config:23
+//! cmask=config:24-31
+//! ```
+//!
+//! # Example
+//! ```no_run
+//! use perfcnt::linux::amd;
+//! use perfcnt::linux::PerfCounterBuilderLinux;
+//!
+//! let event = amd::find_zen_event("ex_ret_instr").expect("Unknown event");
+//! let pc = PerfCounterBuilderLinux::from_amd_event(event)
+//!     .expect("Invalid event")
+//!     .finish();
+//! ```
+
+use std::io;
+use std::io::Error;
+
+/// A raw core event of an AMD processor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmdEvent {
+    /// Name of the event (as used in the PPR and by perf).
+    pub name: &'static str,
+    /// The (extended) event select, 12 bits.
+    pub event_select: u16,
+    /// The unit mask.
+    pub unit_mask: u8,
+    /// Count cycles in which at least `counter_mask` events happened (0 = count events).
+    pub counter_mask: u8,
+    /// Count rising edges of the condition (only meaningful with `counter_mask`).
+    pub edge: bool,
+    /// Invert the `counter_mask` comparison.
+    pub invert: bool,
+    /// What the event counts.
+    pub description: &'static str,
+}
+
+impl AmdEvent {
+    /// A plain event that counts occurrences.
+    pub const fn new(
+        name: &'static str,
+        event_select: u16,
+        unit_mask: u8,
+        description: &'static str,
+    ) -> AmdEvent {
+        AmdEvent {
+            name,
+            event_select,
+            unit_mask,
+            counter_mask: 0,
+            edge: false,
+            invert: false,
+            description,
+        }
+    }
+
+    /// The value for `EventAttr.config` of a `PERF_TYPE_RAW` event.
+    pub fn config(&self) -> Result<u64, io::Error> {
+        if self.event_select > 0xfff {
+            return Err(Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Event select {:#x} of {} does not fit into 12 bits",
+                    self.event_select, self.name
+                ),
+            ));
+        }
+
+        let mut config: u64 = 0;
+        config |= (self.event_select & 0xff) as u64;
+        config |= ((self.event_select >> 8) as u64) << 32;
+        config |= (self.unit_mask as u64) << 8;
+        config |= (self.counter_mask as u64) << 24;
+        if self.edge {
+            config |= 1 << 18;
+        }
+        if self.invert {
+            config |= 1 << 23;
+        }
+        Ok(config)
+    }
+}
+
+/// Common core events of Zen processors.
+pub static ZEN_EVENTS: [AmdEvent; 28] = [
+    AmdEvent::new(
+        "ls_dc_accesses",
+        0x40,
+        0x00,
+        "Number of accesses to the data cache for load and store references.",
+    ),
+    AmdEvent::new("ls_l1_d_tlb_miss.all", 0x45, 0xff, "All L1 DTLB misses."),
+    AmdEvent::new("ls_int_taken", 0x2c, 0x00, "Number of interrupts taken."),
+    AmdEvent::new("ls_not_halted_cyc", 0x76, 0x00, "Cycles not in halt."),
+    AmdEvent::new(
+        "l2_request_g1.all_no_prefetch",
+        0x60,
+        0xf9,
+        "All L2 cache requests, except prefetches.",
+    ),
+    AmdEvent::new(
+        "l2_pf_hit_l2",
+        0x70,
+        0xff,
+        "L2 prefetches that hit in the L2.",
+    ),
+    AmdEvent::new(
+        "l2_pf_miss_l2_hit_l3",
+        0x71,
+        0xff,
+        "L2 prefetches that miss the L2 and hit in the L3.",
+    ),
+    AmdEvent::new(
+        "l2_pf_miss_l2_l3",
+        0x72,
+        0xff,
+        "L2 prefetches that miss the L2 and the L3.",
+    ),
+    AmdEvent::new(
+        "ic_fw32",
+        0x80,
+        0x00,
+        "Number of 32-byte instruction cache fetches.",
+    ),
+    AmdEvent::new(
+        "ic_fw32_miss",
+        0x81,
+        0x00,
+        "Number of 32-byte instruction cache fetches that missed.",
+    ),
+    AmdEvent::new(
+        "ic_cache_fill_l2",
+        0x82,
+        0x00,
+        "Instruction cache lines filled from the L2.",
+    ),
+    AmdEvent::new(
+        "ic_cache_fill_sys",
+        0x83,
+        0x00,
+        "Instruction cache lines filled from the system.",
+    ),
+    AmdEvent::new(
+        "bp_l1_tlb_miss_l2_tlb_hit",
+        0x84,
+        0x00,
+        "Instruction fetches that miss the L1 ITLB but hit the L2 ITLB.",
+    ),
+    AmdEvent::new("ex_ret_instr", 0xc0, 0x00, "Retired instructions."),
+    AmdEvent::new("ex_ret_ops", 0xc1, 0x00, "Retired macro-ops."),
+    AmdEvent::new("ex_ret_brn", 0xc2, 0x00, "Retired branch instructions."),
+    AmdEvent::new(
+        "ex_ret_brn_misp",
+        0xc3,
+        0x00,
+        "Retired branch instructions that were mispredicted.",
+    ),
+    AmdEvent::new(
+        "ex_ret_brn_tkn",
+        0xc4,
+        0x00,
+        "Retired taken branch instructions.",
+    ),
+    AmdEvent::new(
+        "ex_ret_brn_tkn_misp",
+        0xc5,
+        0x00,
+        "Retired taken branch instructions that were mispredicted.",
+    ),
+    AmdEvent::new(
+        "ex_ret_brn_far",
+        0xc6,
+        0x00,
+        "Retired far control transfers.",
+    ),
+    AmdEvent::new("ex_ret_near_ret", 0xc8, 0x00, "Retired near returns."),
+    AmdEvent::new(
+        "ex_ret_near_ret_mispred",
+        0xc9,
+        0x00,
+        "Retired near returns that were mispredicted.",
+    ),
+    AmdEvent::new(
+        "ex_ret_brn_ind_misp",
+        0xca,
+        0x00,
+        "Retired indirect branch instructions that were mispredicted.",
+    ),
+    AmdEvent::new(
+        "ex_ret_cond",
+        0xd1,
+        0x00,
+        "Retired conditional branch instructions.",
+    ),
+    AmdEvent::new(
+        "ex_ret_fused_instr",
+        0x1d0,
+        0x00,
+        "Retired fused instructions.",
+    ),
+    AmdEvent::new(
+        "ic_tag_hit_miss.all_instruction_cache_accesses",
+        0x18e,
+        0x1f,
+        "All instruction cache accesses.",
+    ),
+    AmdEvent::new(
+        "ic_tag_hit_miss.instruction_cache_miss",
+        0x18e,
+        0x18,
+        "Instruction cache misses.",
+    ),
+    AmdEvent::new(
+        "op_cache_hit_miss.all_op_cache_accesses",
+        0x28f,
+        0x07,
+        "All op cache accesses.",
+    ),
+];
+
+/// Find a Zen core event by its name.
+pub fn find_zen_event(name: &str) -> Option<&'static AmdEvent> {
+    ZEN_EVENTS.iter().find(|e| e.name == name)
+}
@@ -0,0 +1,364 @@
+//! Architected common events of the Arm Performance Monitors Extension (PMUv3).
+//!
+//! Events 0x00 - 0x3f and the extended common events from 0x4000 on are defined by
+//! the architecture, so they have the same number on every core that implements them
+//! (which ones are implemented is advertised in `PMCEID0_EL0`/`PMCEID2_EL0` and listed
+//! in `/sys/bus/event_source/devices/armv8_pmuv3*/events`). The kernel's PMUv3 driver
+//! takes the event number as is:
+//!
+//! ```text
+//! event=config:0-15
+//! ```
+//!
+//! # Example
+//! ```no_run
+//! use perfcnt::linux::arm;
+//! use perfcnt::linux::PerfCounterBuilderLinux;
+//!
+//! let event = arm::find_pmuv3_event("L1D_CACHE_REFILL").expect("Unknown event");
+//! let pc = PerfCounterBuilderLinux::from_arm_event(event).finish();
+//! ```
+
+use super::perf_format::{EventAttr, EventAttrType};
+
+/// Bits of `config` that hold the event number.
+const EVENT_MASK: u64 = 0xffff;
+
+/// An architected common event of PMUv3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArmPmuV3Event {
+    /// Mnemonic of the event, as used in the Arm ARM.
+    pub name: &'static str,
+    /// The event number.
+    pub number: u16,
+    /// What the event counts.
+    pub description: &'static str,
+}
+
+impl ArmPmuV3Event {
+    /// An event with the given `number`.
+    pub const fn new(name: &'static str, number: u16, description: &'static str) -> ArmPmuV3Event {
+        ArmPmuV3Event {
+            name,
+            number,
+            description,
+        }
+    }
+
+    /// The value for `EventAttr.config` of a `PERF_TYPE_RAW` event.
+    pub fn config(&self) -> u64 {
+        self.number as u64
+    }
+
+    /// Find the architected event a raw `config` refers to.
+    ///
+    /// Bits outside of the event number are ignored.
+    pub fn from_config(config: u64) -> Option<&'static ArmPmuV3Event> {
+        let number = config & EVENT_MASK;
+        ARM_PMUV3_EVENTS
+            .iter()
+            .chain(ARM_PMUV3_EXTENDED_EVENTS.iter())
+            .find(|e| e.number as u64 == number)
+    }
+
+    /// Decode the event of `attr` (e.g., from a perf.data file recorded on aarch64).
+    ///
+    /// Returns `None` if `attr` is not a raw event or not an architected common event.
+    /// Events opened on the PMU itself (`armv8_pmuv3_0/event=0x8/`) have the dynamic
+    /// type of that PMU, decode them with `from_pmu_event_attr`.
+    pub fn from_event_attr(attr: &EventAttr) -> Option<&'static ArmPmuV3Event> {
+        match attr.attr_type() {
+            EventAttrType::Raw => ArmPmuV3Event::from_config(attr.config),
+            _ => None,
+        }
+    }
+
+    /// Like `from_event_attr`, but also decodes events of the PMUv3 PMU with type
+    /// `pmu_type` (`Pmu::pmu_type` of an `armv8_pmuv3_*` PMU, or the `type` of it in
+    /// sysfs on the machine that recorded the events).
+    pub fn from_pmu_event_attr(attr: &EventAttr, pmu_type: u32) -> Option<&'static ArmPmuV3Event> {
+        if attr.attr_type == pmu_type {
+            ArmPmuV3Event::from_config(attr.config)
+        } else {
+            ArmPmuV3Event::from_event_attr(attr)
+        }
+    }
+}
+
+/// All architected common events, ordered by event number.
+pub static ARM_PMUV3_EVENTS: [ArmPmuV3Event; 64] = [
+    ArmPmuV3Event::new(
+        "SW_INCR",
+        0x00,
+        "Instruction architecturally executed, software increment.",
+    ),
+    ArmPmuV3Event::new(
+        "L1I_CACHE_REFILL",
+        0x01,
+        "Level 1 instruction cache refill.",
+    ),
+    ArmPmuV3Event::new("L1I_TLB_REFILL", 0x02, "Level 1 instruction TLB refill."),
+    ArmPmuV3Event::new("L1D_CACHE_REFILL", 0x03, "Level 1 data cache refill."),
+    ArmPmuV3Event::new("L1D_CACHE", 0x04, "Level 1 data cache access."),
+    ArmPmuV3Event::new("L1D_TLB_REFILL", 0x05, "Level 1 data TLB refill."),
+    ArmPmuV3Event::new(
+        "LD_RETIRED",
+        0x06,
+        "Load instruction architecturally executed.",
+    ),
+    ArmPmuV3Event::new(
+        "ST_RETIRED",
+        0x07,
+        "Store instruction architecturally executed.",
+    ),
+    ArmPmuV3Event::new(
+        "INST_RETIRED",
+        0x08,
+        "Instruction architecturally executed.",
+    ),
+    ArmPmuV3Event::new("EXC_TAKEN", 0x09, "Exception taken."),
+    ArmPmuV3Event::new(
+        "EXC_RETURN",
+        0x0a,
+        "Exception return architecturally executed.",
+    ),
+    ArmPmuV3Event::new(
+        "CID_WRITE_RETIRED",
+        0x0b,
+        "Write to CONTEXTIDR architecturally executed.",
+    ),
+    ArmPmuV3Event::new(
+        "PC_WRITE_RETIRED",
+        0x0c,
+        "Software change of the PC architecturally executed.",
+    ),
+    ArmPmuV3Event::new(
+        "BR_IMMED_RETIRED",
+        0x0d,
+        "Immediate branch architecturally executed.",
+    ),
+    ArmPmuV3Event::new(
+        "BR_RETURN_RETIRED",
+        0x0e,
+        "Function return architecturally executed.",
+    ),
+    ArmPmuV3Event::new(
+        "UNALIGNED_LDST_RETIRED",
+        0x0f,
+        "Unaligned load or store architecturally executed.",
+    ),
+    ArmPmuV3Event::new(
+        "BR_MIS_PRED",
+        0x10,
+        "Mispredicted or not predicted branch speculatively executed.",
+    ),
+    ArmPmuV3Event::new("CPU_CYCLES", 0x11, "Cycles."),
+    ArmPmuV3Event::new(
+        "BR_PRED",
+        0x12,
+        "Predictable branch speculatively executed.",
+    ),
+    ArmPmuV3Event::new("MEM_ACCESS", 0x13, "Data memory access."),
+    ArmPmuV3Event::new("L1I_CACHE", 0x14, "Level 1 instruction cache access."),
+    ArmPmuV3Event::new("L1D_CACHE_WB", 0x15, "Level 1 data cache write-back."),
+    ArmPmuV3Event::new("L2D_CACHE", 0x16, "Level 2 data cache access."),
+    ArmPmuV3Event::new("L2D_CACHE_REFILL", 0x17, "Level 2 data cache refill."),
+    ArmPmuV3Event::new("L2D_CACHE_WB", 0x18, "Level 2 data cache write-back."),
+    ArmPmuV3Event::new("BUS_ACCESS", 0x19, "Bus access."),
+    ArmPmuV3Event::new("MEMORY_ERROR", 0x1a, "Local memory error."),
+    ArmPmuV3Event::new("INST_SPEC", 0x1b, "Operation speculatively executed."),
+    ArmPmuV3Event::new(
+        "TTBR_WRITE_RETIRED",
+        0x1c,
+        "Write to TTBR architecturally executed.",
+    ),
+    ArmPmuV3Event::new("BUS_CYCLES", 0x1d, "Bus cycles."),
+    ArmPmuV3Event::new("CHAIN", 0x1e, "Odd performance counter chain mode."),
+    ArmPmuV3Event::new(
+        "L1D_CACHE_ALLOCATE",
+        0x1f,
+        "Level 1 data cache allocation without refill.",
+    ),
+    ArmPmuV3Event::new(
+        "L2D_CACHE_ALLOCATE",
+        0x20,
+        "Level 2 data cache allocation without refill.",
+    ),
+    ArmPmuV3Event::new(
+        "BR_RETIRED",
+        0x21,
+        "Branch instruction architecturally executed.",
+    ),
+    ArmPmuV3Event::new(
+        "BR_MIS_PRED_RETIRED",
+        0x22,
+        "Mispredicted branch instruction architecturally executed.",
+    ),
+    ArmPmuV3Event::new(
+        "STALL_FRONTEND",
+        0x23,
+        "No operation issued due to the frontend.",
+    ),
+    ArmPmuV3Event::new(
+        "STALL_BACKEND",
+        0x24,
+        "No operation issued due to the backend.",
+    ),
+    ArmPmuV3Event::new("L1D_TLB", 0x25, "Level 1 data TLB access."),
+    ArmPmuV3Event::new("L1I_TLB", 0x26, "Level 1 instruction TLB access."),
+    ArmPmuV3Event::new("L2I_CACHE", 0x27, "Level 2 instruction cache access."),
+    ArmPmuV3Event::new(
+        "L2I_CACHE_REFILL",
+        0x28,
+        "Level 2 instruction cache refill.",
+    ),
+    ArmPmuV3Event::new(
+        "L3D_CACHE_ALLOCATE",
+        0x29,
+        "Level 3 data cache allocation without refill.",
+    ),
+    ArmPmuV3Event::new("L3D_CACHE_REFILL", 0x2a, "Level 3 data cache refill."),
+    ArmPmuV3Event::new("L3D_CACHE", 0x2b, "Level 3 data cache access."),
+    ArmPmuV3Event::new("L3D_CACHE_WB", 0x2c, "Level 3 data cache write-back."),
+    ArmPmuV3Event::new("L2D_TLB_REFILL", 0x2d, "Level 2 data TLB refill."),
+    ArmPmuV3Event::new("L2I_TLB_REFILL", 0x2e, "Level 2 instruction TLB refill."),
+    ArmPmuV3Event::new("L2D_TLB", 0x2f, "Level 2 data TLB access."),
+    ArmPmuV3Event::new("L2I_TLB", 0x30, "Level 2 instruction TLB access."),
+    ArmPmuV3Event::new(
+        "REMOTE_ACCESS",
+        0x31,
+        "Access to another socket in a multi-socket system.",
+    ),
+    ArmPmuV3Event::new("LL_CACHE", 0x32, "Last level cache access."),
+    ArmPmuV3Event::new("LL_CACHE_MISS", 0x33, "Last level cache miss."),
+    ArmPmuV3Event::new(
+        "DTLB_WALK",
+        0x34,
+        "Data TLB access with at least one translation table walk.",
+    ),
+    ArmPmuV3Event::new(
+        "ITLB_WALK",
+        0x35,
+        "Instruction TLB access with at least one translation table walk.",
+    ),
+    ArmPmuV3Event::new("LL_CACHE_RD", 0x36, "Last level cache access, read."),
+    ArmPmuV3Event::new("LL_CACHE_MISS_RD", 0x37, "Last level cache miss, read."),
+    ArmPmuV3Event::new(
+        "REMOTE_ACCESS_RD",
+        0x38,
+        "Access to another socket in a multi-socket system, read.",
+    ),
+    ArmPmuV3Event::new(
+        "L1D_CACHE_LMISS_RD",
+        0x39,
+        "Level 1 data cache long-latency read miss.",
+    ),
+    ArmPmuV3Event::new(
+        "OP_RETIRED",
+        0x3a,
+        "Micro-operation architecturally executed.",
+    ),
+    ArmPmuV3Event::new("OP_SPEC", 0x3b, "Micro-operation speculatively executed."),
+    ArmPmuV3Event::new("STALL", 0x3c, "No operation sent for execution."),
+    ArmPmuV3Event::new(
+        "STALL_SLOT_BACKEND",
+        0x3d,
+        "No operation sent for execution on a slot due to the backend.",
+    ),
+    ArmPmuV3Event::new(
+        "STALL_SLOT_FRONTEND",
+        0x3e,
+        "No operation sent for execution on a slot due to the frontend.",
+    ),
+    ArmPmuV3Event::new(
+        "STALL_SLOT",
+        0x3f,
+        "No operation sent for execution on a slot.",
+    ),
+];
+
+/// Extended common events (PMUv3 for Armv8.1 and later), ordered by event number.
+pub static ARM_PMUV3_EXTENDED_EVENTS: [ArmPmuV3Event; 22] = [
+    ArmPmuV3Event::new(
+        "SAMPLE_POP",
+        0x4000,
+        "Statistical Profiling sample population.",
+    ),
+    ArmPmuV3Event::new("SAMPLE_FEED", 0x4001, "Statistical Profiling sample taken."),
+    ArmPmuV3Event::new(
+        "SAMPLE_FILTRATE",
+        0x4002,
+        "Statistical Profiling sample taken and not removed by filtering.",
+    ),
+    ArmPmuV3Event::new(
+        "SAMPLE_COLLISION",
+        0x4003,
+        "Statistical Profiling sample collided with previous sample.",
+    ),
+    ArmPmuV3Event::new("CNT_CYCLES", 0x4004, "Constant frequency cycles."),
+    ArmPmuV3Event::new("STALL_BACKEND_MEM", 0x4005, "Memory stall cycles."),
+    ArmPmuV3Event::new(
+        "L1I_CACHE_LMISS",
+        0x4006,
+        "Level 1 instruction cache long-latency miss.",
+    ),
+    ArmPmuV3Event::new(
+        "L2D_CACHE_LMISS_RD",
+        0x4009,
+        "Level 2 data cache long-latency read miss.",
+    ),
+    ArmPmuV3Event::new(
+        "L2I_CACHE_LMISS",
+        0x400a,
+        "Level 2 instruction cache long-latency miss.",
+    ),
+    ArmPmuV3Event::new(
+        "L3D_CACHE_LMISS_RD",
+        0x400b,
+        "Level 3 data cache long-latency read miss.",
+    ),
+    ArmPmuV3Event::new(
+        "TRB_WRAP",
+        0x400c,
+        "Trace buffer current write pointer wrapped.",
+    ),
+    ArmPmuV3Event::new(
+        "PMU_OVFS",
+        0x400d,
+        "PMU overflow, counters accessible to EL1 and EL0.",
+    ),
+    ArmPmuV3Event::new("TRB_TRIG", 0x400e, "Trace buffer Trigger Event."),
+    ArmPmuV3Event::new(
+        "PMU_HOVFS",
+        0x400f,
+        "PMU overflow, counters reserved for use by EL2.",
+    ),
+    ArmPmuV3Event::new("TRCEXTOUT0", 0x4010, "PE Trace Unit external output 0."),
+    ArmPmuV3Event::new("TRCEXTOUT1", 0x4011, "PE Trace Unit external output 1."),
+    ArmPmuV3Event::new("TRCEXTOUT2", 0x4012, "PE Trace Unit external output 2."),
+    ArmPmuV3Event::new("TRCEXTOUT3", 0x4013, "PE Trace Unit external output 3."),
+    ArmPmuV3Event::new(
+        "LDST_ALIGN_LAT",
+        0x4020,
+        "Access with additional latency from alignment.",
+    ),
+    ArmPmuV3Event::new(
+        "LD_ALIGN_LAT",
+        0x4021,
+        "Load with additional latency from alignment.",
+    ),
+    ArmPmuV3Event::new(
+        "ST_ALIGN_LAT",
+        0x4022,
+        "Store with additional latency from alignment.",
+    ),
+    ArmPmuV3Event::new("MEM_ACCESS_CHECKED", 0x4024, "Checked data memory access."),
+];
+
+/// Find a PMUv3 common event by its name (e.g., `INST_RETIRED`).
+pub fn find_pmuv3_event(name: &str) -> Option<&'static ArmPmuV3Event> {
+    ARM_PMUV3_EVENTS
+        .iter()
+        .chain(ARM_PMUV3_EXTENDED_EVENTS.iter())
+        .find(|e| e.name == name)
+}
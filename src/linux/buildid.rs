@@ -0,0 +1,157 @@
+//! Finds the exact binaries a recording was taken with, by their build id.
+//!
+//! `perf record` stores the GNU build id of every file that was hit by a sample in
+//! the header of perf.data (see `PerfFile::get_build_ids`) and copies the files to its
+//! build id cache (`~/.debug`, filled by `perf buildid-cache` or `perf archive`).
+//! The `BuildIdResolver` looks for a file with the recorded build id in:
+//!
+//! * `<dir>/.build-id/<xx>/<yyyy...>` (a file, or a directory with an `elf` file)
+//!   of every debug directory,
+//! * the recorded path, if the build id of the file there still matches,
+//! * `<dir>/.build-id/<xx>/<yyyy...>.debug` of every debug directory, the separate
+//!   debug info of a binary (it has the symbols, but not the code).
+//!
+//! So a profile copied from another machine is symbolized with the binaries that
+//! ran there (e.g., unpacked from `perf archive` into `~/.debug`) and not with
+//! whatever is installed at the same path locally.
+//!
+//! # Example
+//! ```no_run
+//! use perfcnt::linux::buildid::BuildIdResolver;
+//! use perfcnt::linux::perf_file::PerfFile;
+//! use perfcnt::linux::symbolize::Symbolizer;
+//!
+//! let pf = PerfFile::new(std::fs::read("perf.data").expect("Can not read perf.data"));
+//! let mut resolver = BuildIdResolver::new();
+//! resolver.add_debug_dir("/srv/profiles/host1/.debug");
+//!
+//! let mut symbolizer = Symbolizer::new();
+//! symbolizer.add_build_ids(&pf.get_build_ids(), &resolver);
+//! ```
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use object::Object;
+
+use super::perf_format::BuildIdRecord;
+
+/// The GNU build id (`.note.gnu.build-id`) of the ELF file `data`.
+pub fn read_build_id(data: &[u8]) -> Option<Vec<u8>> {
+    let file = object::File::parse(data).ok()?;
+    file.build_id().ok()?.map(|id| id.to_vec())
+}
+
+/// `build_id` as lower case hex string.
+pub fn build_id_hex(build_id: &[u8]) -> String {
+    build_id.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Locates binaries by their build id.
+#[derive(Debug, Clone)]
+pub struct BuildIdResolver {
+    debug_dirs: Vec<PathBuf>,
+    use_recorded_path: bool,
+}
+
+impl Default for BuildIdResolver {
+    fn default() -> BuildIdResolver {
+        BuildIdResolver::new()
+    }
+}
+
+impl BuildIdResolver {
+    /// Searches the perf build id cache (`$HOME/.debug`) and the recorded paths.
+    pub fn new() -> BuildIdResolver {
+        let mut resolver = BuildIdResolver::empty();
+        if let Some(home) = env::var_os("HOME") {
+            resolver.add_debug_dir(Path::new(&home).join(".debug"));
+        }
+        resolver
+    }
+
+    /// Searches only the recorded paths, add debug directories with `add_debug_dir`.
+    pub fn empty() -> BuildIdResolver {
+        BuildIdResolver {
+            debug_dirs: Vec::new(),
+            use_recorded_path: true,
+        }
+    }
+
+    /// Also search `dir` (a directory with a `.build-id` subdirectory, e.g., a
+    /// `~/.debug` from another machine or `/usr/lib/debug`), directories added
+    /// first are searched first.
+    pub fn add_debug_dir<P: AsRef<Path>>(&mut self, dir: P) {
+        self.debug_dirs.push(dir.as_ref().to_path_buf());
+    }
+
+    pub fn debug_dirs(&self) -> &[PathBuf] {
+        &self.debug_dirs
+    }
+
+    /// Should the file at the recorded path be used if its build id matches
+    /// (default: true)?
+    pub fn set_use_recorded_path(&mut self, use_recorded_path: bool) {
+        self.use_recorded_path = use_recorded_path;
+    }
+
+    /// The file with `build_id`, the binary was recorded as `recorded_path`.
+    ///
+    /// Returns `None` if no file with that build id was found.
+    pub fn locate(&self, build_id: &[u8], recorded_path: &str) -> Option<PathBuf> {
+        self.locate_with_data(build_id, recorded_path)
+            .map(|(path, _)| path)
+    }
+
+    /// Like `locate` for the file of a BUILD_ID record.
+    pub fn locate_record(&self, record: &BuildIdRecord) -> Option<PathBuf> {
+        self.locate(&record.build_id, &record.filename)
+    }
+
+    /// Finds the file with `build_id` and returns its path and contents.
+    pub fn locate_with_data(
+        &self,
+        build_id: &[u8],
+        recorded_path: &str,
+    ) -> Option<(PathBuf, Vec<u8>)> {
+        self.locate_all(build_id, recorded_path).next()
+    }
+
+    /// All files with `build_id` and their contents, binaries come before separate
+    /// debug info files (`.debug`, they have symbols but no code or `.eh_frame`).
+    pub fn locate_all<'a>(
+        &'a self,
+        build_id: &'a [u8],
+        recorded_path: &str,
+    ) -> impl Iterator<Item = (PathBuf, Vec<u8>)> + 'a {
+        let mut binaries = Vec::new();
+        let mut debug_files = Vec::new();
+        if !build_id.is_empty() {
+            let hex = build_id_hex(build_id);
+            let (dir, file) = hex.split_at(2);
+            for debug_dir in self.debug_dirs.iter() {
+                let path = debug_dir.join(".build-id").join(dir).join(file);
+                // Newer perf versions link to a directory with the binary in `elf`:
+                binaries.push(path.join("elf"));
+                debug_files.push(path.with_extension("debug"));
+                binaries.push(path);
+            }
+            if self.use_recorded_path && recorded_path.starts_with('/') {
+                binaries.push(PathBuf::from(recorded_path));
+            }
+        }
+
+        binaries
+            .into_iter()
+            .chain(debug_files)
+            .filter_map(move |path| {
+                let data = fs::read(&path).ok()?;
+                if read_build_id(&data)?.as_slice() == build_id {
+                    Some((path, data))
+                } else {
+                    None
+                }
+            })
+    }
+}
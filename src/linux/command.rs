@@ -0,0 +1,162 @@
+//! Count events for a child process from the moment it starts until it exits,
+//! similar to what `perf stat <command>` does.
+//!
+//! # Example
+//! ```no_run
+//! use std::process::Command;
+//! use perfcnt::linux::{HardwareEventType, PerfCounterBuilderLinux};
+//! use perfcnt::linux::command::measure_command;
+//!
+//! let events = [
+//!     PerfCounterBuilderLinux::from_hardware_event(HardwareEventType::CPUCycles),
+//!     PerfCounterBuilderLinux::from_hardware_event(HardwareEventType::Instructions),
+//! ];
+//! let m = measure_command(Command::new("ls"), &events).expect("Can not measure ls");
+//! println!("ls exited with {}", m.status);
+//! for value in m.values.iter() {
+//!     println!("{:?}", value.scaled);
+//! }
+//! ```
+
+use std::fs::File;
+use std::io;
+use std::io::{Error, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, ExitStatus};
+use std::thread;
+
+use libc::pid_t;
+
+use super::perf_format::ScaledValue;
+use super::{PerfCounter, PerfCounterBuilderLinux};
+
+/// The outcome of `measure_command`.
+#[derive(Debug)]
+pub struct CommandMeasurement {
+    /// How the command exited.
+    pub status: ExitStatus,
+    /// Final counts of the command and all its descendants, in the order the events were given.
+    pub values: Vec<ScaledValue>,
+}
+
+/// A pipe as (read end, write end), both closed on exec.
+fn pipe() -> Result<(libc::c_int, libc::c_int), io::Error> {
+    let mut fds: [libc::c_int; 2] = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } == -1 {
+        return Err(Error::last_os_error());
+    }
+    Ok((fds[0], fds[1]))
+}
+
+/// Run `command` and count `events` for it (and every process it spawns) until it exits.
+///
+/// The forked child reports its pid and waits (before `exec`) until we attached the
+/// counters. They are enabled by the `exec`, so nothing of the new program is missed
+/// and nothing of the set-up before is counted. Afterwards we wait for the child to exit.
+///
+/// The events are opened for the child with `enable_on_exec`, `inherit` and the timing
+/// information for `read_scaled` set, regardless of the respective settings in the
+/// builders.
+pub fn measure_command(
+    mut command: Command,
+    events: &[PerfCounterBuilderLinux],
+) -> Result<CommandMeasurement, io::Error> {
+    let (pid_read, pid_write) = pipe()?;
+    let mut pid_read = unsafe { File::from_raw_fd(pid_read) };
+    let pid_write = unsafe { File::from_raw_fd(pid_write) };
+    let (go_read, go_write) = pipe()?;
+    let go_read = unsafe { File::from_raw_fd(go_read) };
+    let mut go_write = unsafe { File::from_raw_fd(go_write) };
+
+    let (child_pid_write, child_go_read, child_go_write) = (
+        pid_write.as_raw_fd(),
+        go_read.as_raw_fd(),
+        go_write.as_raw_fd(),
+    );
+    unsafe {
+        // Runs in the forked child, only async-signal-safe calls:
+        command.pre_exec(move || {
+            // Our copy would keep the pipe open if the parent gives up:
+            libc::close(child_go_write);
+            let pid = libc::getpid().to_ne_bytes();
+            if libc::write(
+                child_pid_write,
+                pid.as_ptr() as *const libc::c_void,
+                pid.len(),
+            ) != pid.len() as isize
+            {
+                return Err(Error::last_os_error());
+            }
+            let mut go = 0u8;
+            if libc::read(child_go_read, &mut go as *mut u8 as *mut libc::c_void, 1) != 1 {
+                return Err(Error::new(
+                    io::ErrorKind::Interrupted,
+                    "Measurement of the command was aborted",
+                ));
+            }
+            Ok(())
+        });
+    }
+
+    // `spawn` only returns once the child called `exec`, so it has to wait elsewhere:
+    // (the child inherits the pipe ends, they have to stay open until it is forked)
+    let spawner = thread::spawn(move || {
+        let child = command.spawn();
+        // Lets the read of the pid fail if the child could not be forked:
+        drop(pid_write);
+        drop(go_read);
+        child
+    });
+
+    let mut pid = [0u8; 4];
+    let forked = pid_read.read_exact(&mut pid);
+    let counters = match forked {
+        Ok(()) => open_counters(pid_t::from_ne_bytes(pid), events)
+            // Release the child:
+            .and_then(|counters| go_write.write_all(&[1]).map(|_| counters)),
+        Err(ref e) => Err(Error::new(e.kind(), "Command was not forked")),
+    };
+    // Without the go the child fails before `exec`:
+    drop(go_write);
+
+    let child = spawner
+        .join()
+        .map_err(|_| Error::other("Spawning the command panicked"))?;
+    let (mut child, mut counters) = match (child, counters) {
+        (Ok(child), Ok(counters)) => (child, counters),
+        (Ok(mut child), Err(e)) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+        // The child gave up because we could not open the counters:
+        (Err(_), Err(e)) if forked.is_ok() => return Err(e),
+        (Err(e), _) => return Err(e),
+    };
+
+    let status = child.wait()?;
+
+    // Counts of exited children are folded into the inherited counters, so
+    // once everything exited, these are the totals.
+    let values = counters
+        .iter_mut()
+        .map(|pc| pc.read_scaled())
+        .collect::<Result<Vec<ScaledValue>, io::Error>>()?;
+
+    Ok(CommandMeasurement { status, values })
+}
+
+/// Opens `events` for process `pid`, they start counting when it calls `exec`.
+fn open_counters(
+    pid: pid_t,
+    events: &[PerfCounterBuilderLinux],
+) -> Result<Vec<PerfCounter>, io::Error> {
+    let mut counters = Vec::with_capacity(events.len());
+    for event in events {
+        let mut builder = event.clone();
+        builder.for_pid(pid).inherit().disable().enable_on_exec();
+        counters.push(builder.finish()?);
+    }
+    Ok(counters)
+}
@@ -0,0 +1,277 @@
+//! Errors of `perf_event_open`, classified so the caller can tell what to change.
+//!
+//! The kernel only reports an errno, and the same errno has different causes
+//! depending on the event. `PerfError` keeps the attributes the counter was opened
+//! with, and for permission errors it looks at `perf_event_paranoid` and the
+//! capabilities of the process to explain which restriction applies.
+//!
+//! # Example
+//! ```no_run
+//! use perfcnt::linux::error::PerfError;
+//! use perfcnt::linux::{HardwareEventType, PerfCounterBuilderLinux};
+//!
+//! match PerfCounterBuilderLinux::from_hardware_event(HardwareEventType::Instructions).finish() {
+//!     Ok(_pc) => println!("Counter opened"),
+//!     Err(e @ PerfError::PermissionDenied { .. }) => eprintln!("{}", e),
+//!     Err(e) => panic!("Could not open counter: {}", e),
+//! }
+//! ```
+
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::event_str::format_event;
+use super::perf_format::{EventAttr, EventAttrFlags, EventAttrType};
+
+/// Locations relative to the procfs root.
+const PERF_EVENT_PARANOID: &str = "sys/kernel/perf_event_paranoid";
+const PROC_SELF_STATUS: &str = "self/status";
+
+const CAP_SYS_ADMIN: u32 = 21;
+const CAP_PERFMON: u32 = 38;
+
+/// What the kernel lets this process do with perf events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PerfPermissions {
+    /// The value of `/proc/sys/kernel/perf_event_paranoid` (if it could be read).
+    pub paranoid: Option<i32>,
+    /// The effective capabilities of the process (`CapEff` in `/proc/self/status`).
+    pub capabilities: Option<u64>,
+}
+
+impl PerfPermissions {
+    /// Reads the settings that apply to the calling process.
+    pub fn current() -> PerfPermissions {
+        PerfPermissions::from_proc(Path::new("/proc"))
+    }
+
+    /// Reads the settings from a procfs mounted at `proc_root`.
+    pub fn from_proc(proc_root: &Path) -> PerfPermissions {
+        let paranoid = fs::read_to_string(proc_root.join(PERF_EVENT_PARANOID))
+            .ok()
+            .and_then(|s| s.trim().parse().ok());
+        let capabilities = fs::read_to_string(proc_root.join(PROC_SELF_STATUS))
+            .ok()
+            .and_then(|s| {
+                s.lines()
+                    .find_map(|l| l.strip_prefix("CapEff:"))
+                    .and_then(|caps| u64::from_str_radix(caps.trim(), 16).ok())
+            });
+        PerfPermissions {
+            paranoid,
+            capabilities,
+        }
+    }
+
+    /// Does the process have CAP_PERFMON (or CAP_SYS_ADMIN, which kernels before 5.8 check)?
+    pub fn is_privileged(&self) -> bool {
+        let caps = self.capabilities.unwrap_or(0);
+        caps & (1 << CAP_PERFMON) != 0 || caps & (1 << CAP_SYS_ADMIN) != 0
+    }
+}
+
+/// Why a counter could not be opened.
+#[derive(Debug)]
+pub enum PerfError {
+    /// The kernel refused the event (EACCES, EPERM).
+    PermissionDenied {
+        attr: Box<EventAttr>,
+        errno: i32,
+        /// The counter was meant to count all processes (on a CPU or in a cgroup).
+        system_wide: bool,
+        permissions: PerfPermissions,
+    },
+    /// The event does not exist on this CPU or kernel (ENOENT, ENODEV, EOPNOTSUPP).
+    UnsupportedEvent { attr: Box<EventAttr>, errno: i32 },
+    /// The attributes are invalid or contradict each other (EINVAL, E2BIG, EOVERFLOW).
+    ///
+    /// For E2BIG `attr.size` is the size of the attributes the kernel supports, it
+    /// does not know the fields in `attr.fields_after(attr.size)`.
+    InvalidAttributes { attr: Box<EventAttr>, errno: i32 },
+    /// The process or system ran out of file descriptors (EMFILE, ENFILE).
+    TooManyOpenFiles { attr: Box<EventAttr>, errno: i32 },
+    /// Any other error.
+    Io(io::Error),
+}
+
+impl PerfError {
+    /// Classifies the errno `perf_event_open` returned for `attr`.
+    ///
+    /// For permission errors this reads the current `PerfPermissions`.
+    pub fn from_errno(errno: i32, attr: &EventAttr, system_wide: bool) -> PerfError {
+        // Unboxed, the attributes would make every `Result<_, PerfError>` large:
+        let attr = Box::new(*attr);
+        match errno {
+            libc::EACCES | libc::EPERM => PerfError::PermissionDenied {
+                attr,
+                errno,
+                system_wide,
+                permissions: PerfPermissions::current(),
+            },
+            libc::ENOENT | libc::ENODEV | libc::EOPNOTSUPP => {
+                PerfError::UnsupportedEvent { attr, errno }
+            }
+            libc::EINVAL | libc::E2BIG | libc::EOVERFLOW => {
+                PerfError::InvalidAttributes { attr, errno }
+            }
+            libc::EMFILE | libc::ENFILE => PerfError::TooManyOpenFiles { attr, errno },
+            _ => PerfError::Io(io::Error::from_raw_os_error(errno)),
+        }
+    }
+
+    /// The attributes of the event that could not be opened.
+    pub fn attr(&self) -> Option<&EventAttr> {
+        match *self {
+            PerfError::PermissionDenied { ref attr, .. }
+            | PerfError::UnsupportedEvent { ref attr, .. }
+            | PerfError::InvalidAttributes { ref attr, .. }
+            | PerfError::TooManyOpenFiles { ref attr, .. } => Some(attr),
+            PerfError::Io(_) => None,
+        }
+    }
+
+    /// The errno reported by the kernel.
+    pub fn raw_os_error(&self) -> Option<i32> {
+        match *self {
+            PerfError::PermissionDenied { errno, .. }
+            | PerfError::UnsupportedEvent { errno, .. }
+            | PerfError::InvalidAttributes { errno, .. }
+            | PerfError::TooManyOpenFiles { errno, .. } => Some(errno),
+            PerfError::Io(ref e) => e.raw_os_error(),
+        }
+    }
+
+    /// The `io::ErrorKind` that corresponds to this error.
+    pub fn kind(&self) -> io::ErrorKind {
+        match *self {
+            PerfError::Io(ref e) => e.kind(),
+            _ => io::Error::from_raw_os_error(self.raw_os_error().unwrap_or(0)).kind(),
+        }
+    }
+
+    /// Explains which setting keeps the event from being opened.
+    fn permission_hint(
+        attr: &EventAttr,
+        system_wide: bool,
+        permissions: &PerfPermissions,
+    ) -> String {
+        if permissions.is_privileged() {
+            return String::from(
+                "the process has CAP_PERFMON, \
+                 the event may be blocked by a seccomp filter or security module",
+            );
+        }
+
+        let paranoid = match permissions.paranoid {
+            Some(p) => p,
+            None => {
+                return String::from(
+                    "kernel.perf_event_paranoid can not be read, grant the process CAP_PERFMON",
+                )
+            }
+        };
+        let counts_kernel = !attr
+            .settings
+            .contains(EventAttrFlags::EVENT_ATTR_EXCLUDE_KERNEL);
+        let raw_tracepoint =
+            attr.attr_type() == EventAttrType::TracePoint && attr.sample_type.has_raw();
+
+        let requirement = if paranoid >= 3 {
+            "disables perf events for unprivileged processes, set it to 2"
+        } else if system_wide && paranoid >= 1 {
+            "measuring all processes requires 0 or lower"
+        } else if counts_kernel && paranoid >= 2 {
+            "counting in kernel mode requires 1 or lower (or use exclude_kernel)"
+        } else if raw_tracepoint && paranoid >= 0 {
+            "raw tracepoint samples require -1"
+        } else {
+            return format!(
+                "kernel.perf_event_paranoid is {} which permits this event, \
+                 it may be blocked by a seccomp filter or security module",
+                paranoid
+            );
+        };
+        format!(
+            "kernel.perf_event_paranoid is {}, {} or grant the process CAP_PERFMON",
+            paranoid, requirement
+        )
+    }
+}
+
+impl fmt::Display for PerfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let os_error = io::Error::from_raw_os_error;
+        match *self {
+            PerfError::PermissionDenied {
+                ref attr,
+                errno,
+                system_wide,
+                ref permissions,
+            } => write!(
+                f,
+                "Can not open {} ({}): {}",
+                format_event(attr, None),
+                os_error(errno),
+                PerfError::permission_hint(attr, system_wide, permissions)
+            ),
+            PerfError::UnsupportedEvent { ref attr, errno } => write!(
+                f,
+                "Event {} is not supported by this CPU or kernel ({})",
+                format_event(attr, None),
+                os_error(errno)
+            ),
+            PerfError::InvalidAttributes { ref attr, errno } if errno == libc::E2BIG => write!(
+                f,
+                "The kernel only supports the first {} bytes of the attributes of {} ({}), \
+                 it does not know {}",
+                attr.size,
+                format_event(attr, None),
+                os_error(errno),
+                attr.fields_after(attr.size).join(", ")
+            ),
+            PerfError::InvalidAttributes { ref attr, errno } => write!(
+                f,
+                "The kernel rejected the attributes of {} ({}), \
+                 check for conflicting flags or sample settings",
+                format_event(attr, None),
+                os_error(errno)
+            ),
+            PerfError::TooManyOpenFiles { ref attr, errno } => write!(
+                f,
+                "Can not open {} ({}), \
+                 raise the file descriptor limit (ulimit -n) or close other counters",
+                format_event(attr, None),
+                os_error(errno)
+            ),
+            PerfError::Io(ref e) => e.fmt(f),
+        }
+    }
+}
+
+impl error::Error for PerfError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            PerfError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PerfError {
+    fn from(e: io::Error) -> PerfError {
+        PerfError::Io(e)
+    }
+}
+
+/// Keeps the `ErrorKind` of the errno, the `PerfError` (and its message) is the inner error.
+impl From<PerfError> for io::Error {
+    fn from(e: PerfError) -> io::Error {
+        match e {
+            PerfError::Io(e) => e,
+            e => io::Error::new(e.kind(), e),
+        }
+    }
+}
@@ -0,0 +1,557 @@
+//! Parse and format events the way `perf` writes them on the command line.
+//!
+//! Supported are:
+//!
+//!  * Generic hardware and software events: `cycles`, `instructions`, `task-clock`, ...
+//!  * Cache events: `L1-dcache-load-misses`, `LLC-loads`, `dTLB-store-misses`, ...
+//!  * Raw events: `r01c2`
+//!  * PMU events: `cpu/event=0x3c,umask=0x0/` or aliases such as `uncore_imc_0/cas_count_read/`
+//!  * Tracepoints: `sched:sched_switch`
+//!  * Breakpoints: `mem:0x1000/8:w`
+//!  * Groups: `{cycles,instructions}`
+//!
+//! Events can be followed by modifiers (e.g., `cycles:u`, `r01c2:pp`, `cpu/event=0x3c/k`):
+//! `u` (user), `k` (kernel), `h` (hypervisor), `G` (guest), `H` (host)
+//! and `p`, `pp`, `ppp` (precise ip).
+//!
+//! # Example
+//! ```no_run
+//! use perfcnt::linux::event_str::{parse_events, ParsedEvent};
+//!
+//! for event in parse_events("cycles:u,{instructions,branch-misses}").expect("Invalid events") {
+//!     match event {
+//!         ParsedEvent::Event(builder) => { let _pc = builder.finish(); }
+//!         ParsedEvent::Group(builder) => { let _group = builder.finish(); }
+//!     }
+//! }
+//! ```
+
+use std::io;
+use std::io::Error;
+
+use super::perf_event;
+use super::perf_format::{EventAttr, EventAttrFlags, EventAttrType};
+use super::pmu::PmuRegistry;
+use super::{
+    BreakpointLen, BreakpointType, CacheId, CacheOpId, CacheOpResultId, HardwareEventType,
+    PerfCounterBuilderLinux, PerfCounterGroupBuilder, SoftwareEventType,
+};
+
+pub(super) const HARDWARE_EVENTS: [(&str, HardwareEventType); 14] = [
+    ("cycles", HardwareEventType::CPUCycles),
+    ("cpu-cycles", HardwareEventType::CPUCycles),
+    ("instructions", HardwareEventType::Instructions),
+    ("cache-references", HardwareEventType::CacheReferences),
+    ("cache-misses", HardwareEventType::CacheMisses),
+    ("branch-instructions", HardwareEventType::BranchInstructions),
+    ("branches", HardwareEventType::BranchInstructions),
+    ("branch-misses", HardwareEventType::BranchMisses),
+    ("bus-cycles", HardwareEventType::BusCycles),
+    (
+        "stalled-cycles-frontend",
+        HardwareEventType::StalledCyclesFrontend,
+    ),
+    (
+        "idle-cycles-frontend",
+        HardwareEventType::StalledCyclesFrontend,
+    ),
+    (
+        "stalled-cycles-backend",
+        HardwareEventType::StalledCyclesBackend,
+    ),
+    (
+        "idle-cycles-backend",
+        HardwareEventType::StalledCyclesBackend,
+    ),
+    ("ref-cycles", HardwareEventType::RefCPUCycles),
+];
+
+const SOFTWARE_EVENTS: [(&str, SoftwareEventType); 12] = [
+    ("cpu-clock", SoftwareEventType::CpuClock),
+    ("task-clock", SoftwareEventType::TaskClock),
+    ("page-faults", SoftwareEventType::PageFaults),
+    ("faults", SoftwareEventType::PageFaults),
+    ("context-switches", SoftwareEventType::ContextSwitches),
+    ("cs", SoftwareEventType::ContextSwitches),
+    ("cpu-migrations", SoftwareEventType::CpuMigrations),
+    ("migrations", SoftwareEventType::CpuMigrations),
+    ("minor-faults", SoftwareEventType::PageFaultsMin),
+    ("major-faults", SoftwareEventType::PageFaultsMaj),
+    ("alignment-faults", SoftwareEventType::AlignmentFaults),
+    ("emulation-faults", SoftwareEventType::EmulationFaults),
+];
+
+pub(super) const CACHES: [(&str, CacheId); 7] = [
+    ("L1-dcache", CacheId::L1D),
+    ("L1-icache", CacheId::L1I),
+    ("LLC", CacheId::LL),
+    ("dTLB", CacheId::DTLB),
+    ("iTLB", CacheId::ITLB),
+    ("branch", CacheId::BPU),
+    ("node", CacheId::NODE),
+];
+
+/// (operation, plural form used for accesses)
+pub(super) const CACHE_OPS: [(&str, &str, CacheOpId); 3] = [
+    ("load", "loads", CacheOpId::Read),
+    ("store", "stores", CacheOpId::Write),
+    ("prefetch", "prefetches", CacheOpId::Prefetch),
+];
+
+fn invalid(msg: String) -> Error {
+    Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// An event or a group of events, as parsed from an event string.
+#[derive(Clone)]
+pub enum ParsedEvent {
+    Event(PerfCounterBuilderLinux),
+    Group(PerfCounterGroupBuilder),
+}
+
+/// The PMUs are only read from sysfs if an event refers to one.
+enum Pmus<'a> {
+    Borrowed(&'a PmuRegistry),
+    Lazy(Option<PmuRegistry>),
+}
+
+impl<'a> Pmus<'a> {
+    fn get(&mut self) -> Result<&PmuRegistry, io::Error> {
+        match self {
+            Pmus::Borrowed(pmus) => Ok(*pmus),
+            Pmus::Lazy(ref mut pmus) => {
+                if pmus.is_none() {
+                    *pmus = Some(PmuRegistry::new()?);
+                }
+                Ok(pmus.as_ref().unwrap())
+            }
+        }
+    }
+}
+
+/// Parse a comma separated list of events and groups, e.g., `cycles:u,{instructions,branches}`.
+pub fn parse_events(events: &str) -> Result<Vec<ParsedEvent>, io::Error> {
+    parse_list(events, &mut Pmus::Lazy(None))
+}
+
+/// Like `parse_events`, but PMU events are resolved with `pmus` instead of the PMUs in `/sys`.
+pub fn parse_events_with_pmus(
+    events: &str,
+    pmus: &PmuRegistry,
+) -> Result<Vec<ParsedEvent>, io::Error> {
+    parse_list(events, &mut Pmus::Borrowed(pmus))
+}
+
+/// Parse a single event (no groups).
+pub fn parse_event(event: &str) -> Result<PerfCounterBuilderLinux, io::Error> {
+    parse_single(event, &mut Pmus::Lazy(None))
+}
+
+/// Like `parse_event`, but PMU events are resolved with `pmus` instead of the PMUs in `/sys`.
+pub fn parse_event_with_pmus(
+    event: &str,
+    pmus: &PmuRegistry,
+) -> Result<PerfCounterBuilderLinux, io::Error> {
+    parse_single(event, &mut Pmus::Borrowed(pmus))
+}
+
+/// Parse a group, e.g., `{cycles,instructions}:u`.
+pub fn parse_group(group: &str) -> Result<PerfCounterGroupBuilder, io::Error> {
+    match parse_list(group, &mut Pmus::Lazy(None))?.as_slice() {
+        [ParsedEvent::Group(g)] => Ok(g.clone()),
+        _ => Err(invalid(format!("'{}' is not a single event group", group))),
+    }
+}
+
+/// Whether the slash at `i` separates address and length of a breakpoint (mem:addr/len).
+/// Unlike the slashes around PMU terms, it is not followed by another one.
+fn is_breakpoint_slash(s: &str, i: usize) -> bool {
+    s[..i]
+        .rsplit([',', '{'])
+        .next()
+        .unwrap_or("")
+        .trim_start()
+        .starts_with("mem:")
+}
+
+/// Splits `s` at commas that separate events (not the ones within PMU terms or groups).
+fn split_events(s: &str) -> Result<Vec<&str>, io::Error> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut in_terms = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' if !in_terms => depth += 1,
+            '}' if !in_terms => {
+                if depth == 0 {
+                    return Err(invalid(format!("Unbalanced '}}' in '{}'", s)));
+                }
+                depth -= 1;
+            }
+            '/' if !is_breakpoint_slash(s, i) => in_terms = !in_terms,
+            ',' if depth == 0 && !in_terms => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 || in_terms {
+        return Err(invalid(format!(
+            "Unterminated group or PMU terms in '{}'",
+            s
+        )));
+    }
+    parts.push(&s[start..]);
+    Ok(parts)
+}
+
+fn parse_list(s: &str, pmus: &mut Pmus) -> Result<Vec<ParsedEvent>, io::Error> {
+    let mut events = Vec::new();
+    for part in split_events(s)? {
+        let part = part.trim();
+        if part.is_empty() {
+            return Err(invalid(format!("Empty event in '{}'", s)));
+        }
+
+        if part.starts_with('{') {
+            let end = part
+                .rfind('}')
+                .ok_or_else(|| invalid(format!("Unterminated group '{}'", part)))?;
+            let modifiers = part[end + 1..].trim_start_matches(':');
+
+            let mut members = Vec::new();
+            for member in split_events(&part[1..end])? {
+                let mut builder = parse_single(member.trim(), pmus)?;
+                apply_modifiers(&mut builder, modifiers)?;
+                members.push(builder);
+            }
+
+            let mut members = members.into_iter();
+            let leader = members
+                .next()
+                .ok_or_else(|| invalid(format!("Empty group '{}'", part)))?;
+            let mut group = PerfCounterGroupBuilder::new(leader);
+            for member in members {
+                group.add_member(member);
+            }
+            events.push(ParsedEvent::Group(group));
+        } else {
+            events.push(ParsedEvent::Event(parse_single(part, pmus)?));
+        }
+    }
+    Ok(events)
+}
+
+fn parse_single(s: &str, pmus: &mut Pmus) -> Result<PerfCounterBuilderLinux, io::Error> {
+    let s = s.trim();
+    if s.contains('{') || s.contains('}') {
+        return Err(invalid(format!("Unexpected group in '{}'", s)));
+    }
+
+    if s.starts_with("mem:") {
+        return parse_breakpoint(s);
+    }
+
+    // PMU events: pmu/terms/modifiers
+    if let Some(start) = s.find('/') {
+        let end = s[start + 1..]
+            .find('/')
+            .map(|e| start + 1 + e)
+            .ok_or_else(|| invalid(format!("Unterminated PMU terms in '{}'", s)))?;
+        let attr = pmus.get()?.event_attr(&s[..start], &s[start + 1..end])?;
+        let mut builder = PerfCounterBuilderLinux::from_event_attr(&attr);
+        apply_modifiers(&mut builder, s[end + 1..].trim_start_matches(':'))?;
+        return Ok(builder);
+    }
+
+    let mut parts = s.splitn(2, ':');
+    let name = parts.next().unwrap_or("");
+    let rest = parts.next().unwrap_or("");
+
+    let mut builder = match named_event(name) {
+        Some(builder) => builder,
+        None if !rest.is_empty() => {
+            // A tracepoint (subsystem:event), possibly followed by modifiers:
+            let mut parts = rest.splitn(2, ':');
+            let event = parts.next().unwrap_or("");
+            let mut builder =
+                PerfCounterBuilderLinux::from_tracepoint(&format!("{}:{}", name, event))?;
+            apply_modifiers(&mut builder, parts.next().unwrap_or(""))?;
+            return Ok(builder);
+        }
+        None => pmu_alias(name, pmus)?,
+    };
+    apply_modifiers(&mut builder, rest)?;
+    Ok(builder)
+}
+
+/// Generic, cache and raw events.
+fn named_event(name: &str) -> Option<PerfCounterBuilderLinux> {
+    if let Some((_, event)) = HARDWARE_EVENTS.iter().find(|(n, _)| *n == name) {
+        return Some(PerfCounterBuilderLinux::from_hardware_event(*event));
+    }
+    if let Some((_, event)) = SOFTWARE_EVENTS.iter().find(|(n, _)| *n == name) {
+        return Some(PerfCounterBuilderLinux::from_software_event(*event));
+    }
+    if name.len() > 1 && name.starts_with('r') && name[1..].chars().all(|c| c.is_ascii_hexdigit()) {
+        let config = u64::from_str_radix(&name[1..], 16).ok()?;
+        return Some(PerfCounterBuilderLinux::from_event_attr(&EventAttr {
+            attr_type: perf_event::PERF_TYPE_RAW,
+            config,
+            ..Default::default()
+        }));
+    }
+    cache_event(name)
+}
+
+/// Cache events: <cache>-<op>s (accesses) or <cache>-<op>-misses.
+fn cache_event(name: &str) -> Option<PerfCounterBuilderLinux> {
+    let (cache_name, cache) = CACHES.iter().find(|(n, _)| {
+        name.len() > n.len() && name.starts_with(n) && name.as_bytes()[n.len()] == b'-'
+    })?;
+    let rest = &name[cache_name.len() + 1..];
+
+    CACHE_OPS.iter().find_map(|(op_name, plural, op)| {
+        if rest == *plural {
+            Some(PerfCounterBuilderLinux::from_cache_event(
+                *cache,
+                *op,
+                CacheOpResultId::Access,
+            ))
+        } else if rest.len() == op_name.len() + "-misses".len()
+            && rest.starts_with(op_name)
+            && rest.ends_with("-misses")
+        {
+            Some(PerfCounterBuilderLinux::from_cache_event(
+                *cache,
+                *op,
+                CacheOpResultId::Miss,
+            ))
+        } else {
+            None
+        }
+    })
+}
+
+/// Events that are only known as aliases of a PMU (e.g., `mem-loads` of the `cpu` PMU).
+fn pmu_alias(name: &str, pmus: &mut Pmus) -> Result<PerfCounterBuilderLinux, io::Error> {
+    let unknown = || Error::new(io::ErrorKind::NotFound, format!("Unknown event '{}'", name));
+    if name.is_empty() {
+        return Err(invalid("Empty event name".to_string()));
+    }
+
+    let pmus = pmus.get().map_err(|_| unknown())?;
+    let pmu = pmus
+        .pmus()
+        .find(|pmu| pmu.events.contains_key(name))
+        .ok_or_else(unknown)?;
+    Ok(PerfCounterBuilderLinux::from_event_attr(
+        &pmu.event_attr(name)?,
+    ))
+}
+
+/// mem:<addr>[/<len>][:<access>]
+fn parse_breakpoint(s: &str) -> Result<PerfCounterBuilderLinux, io::Error> {
+    let err = || invalid(format!("Invalid breakpoint '{}'", s));
+    let mut parts = s["mem:".len()..].splitn(2, ':');
+    let mut location = parts.next().unwrap_or("").splitn(2, '/');
+
+    let addr = location.next().unwrap_or("");
+    let addr = match addr.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => addr.parse(),
+    }
+    .map_err(|_| err())?;
+
+    let len = match location.next() {
+        None | Some("8") => BreakpointLen::Eight,
+        Some("4") => BreakpointLen::Four,
+        Some("2") => BreakpointLen::Two,
+        Some("1") => BreakpointLen::One,
+        Some(_) => return Err(err()),
+    };
+
+    let bp_type = match parts.next() {
+        None | Some("rw") | Some("wr") => BreakpointType::ReadWrite,
+        Some("r") => BreakpointType::Read,
+        Some("w") => BreakpointType::Write,
+        Some("x") => BreakpointType::Execute,
+        Some(_) => return Err(err()),
+    };
+
+    Ok(PerfCounterBuilderLinux::from_breakpoint(addr, bp_type, len))
+}
+
+/// Apply u/k/h/G/H/p/pp/ppp modifiers to `builder`.
+fn apply_modifiers(
+    builder: &mut PerfCounterBuilderLinux,
+    modifiers: &str,
+) -> Result<(), io::Error> {
+    let (mut user, mut kernel, mut hv, mut guest, mut host) = (false, false, false, false, false);
+    let mut precise = 0;
+    for c in modifiers.chars() {
+        match c {
+            'u' => user = true,
+            'k' => kernel = true,
+            'h' => hv = true,
+            'G' => guest = true,
+            'H' => host = true,
+            'p' => precise += 1,
+            _ => {
+                return Err(invalid(format!(
+                    "Unknown modifier '{}' in '{}'",
+                    c, modifiers
+                )))
+            }
+        }
+    }
+
+    // Naming a privilege level means we only want to count in the named levels:
+    if user || kernel || hv {
+        if !user {
+            builder.exclude_user();
+        }
+        if !kernel {
+            builder.exclude_kernel();
+        }
+        if !hv {
+            builder.exclude_hv();
+        }
+    }
+    if guest && !host {
+        builder.exclude_host();
+    }
+    if host && !guest {
+        builder.exclude_guest();
+    }
+
+    match precise {
+        0 => {}
+        1 => {
+            builder.set_ip_sample_constant_skid();
+        }
+        2 => {
+            builder.set_ip_sample_req_zero_skid();
+        }
+        3 => {
+            builder.set_ip_sample_zero_skid();
+        }
+        _ => return Err(invalid(format!("Too many 'p' in '{}'", modifiers))),
+    }
+
+    Ok(())
+}
+
+/// Render `attr` as an event string that `parse_event` understands.
+///
+/// Events of dynamic PMUs can only be named if `pmus` is given, otherwise
+/// the PMU type number is used in place of the name.
+pub fn format_event(attr: &EventAttr, pmus: Option<&PmuRegistry>) -> String {
+    let name = match attr.attr_type() {
+        EventAttrType::Hardware => HARDWARE_EVENTS
+            .iter()
+            .find(|(_, e)| *e as u64 == attr.config)
+            .map(|(n, _)| n.to_string()),
+        EventAttrType::Software => SOFTWARE_EVENTS
+            .iter()
+            .find(|(_, e)| *e as u64 == attr.config)
+            .map(|(n, _)| n.to_string()),
+        EventAttrType::HwCache => format_cache_event(attr.config),
+        EventAttrType::Raw => Some(format!("r{:x}", attr.config)),
+        EventAttrType::Breakpoint => {
+            let access = match attr.bp_type {
+                t if t == BreakpointType::Read as u32 => "r",
+                t if t == BreakpointType::Write as u32 => "w",
+                t if t == BreakpointType::Execute as u32 => "x",
+                _ => "rw",
+            };
+            // Breakpoints don't take modifiers:
+            return format!(
+                "mem:{:#x}/{}:{}",
+                attr.config1_or_bp_addr, attr.config2_or_bp_len, access
+            );
+        }
+        _ => None,
+    };
+
+    let name = name.unwrap_or_else(|| {
+        let pmu = pmus
+            .and_then(|pmus| pmus.pmus().find(|p| p.pmu_type == attr.attr_type))
+            .map(|p| p.name.clone())
+            .unwrap_or_else(|| match attr.attr_type() {
+                EventAttrType::TracePoint => String::from("tracepoint"),
+                _ => attr.attr_type.to_string(),
+            });
+        let mut terms = format!("config={:#x}", attr.config);
+        if attr.config1_or_bp_addr != 0 {
+            terms.push_str(&format!(",config1={:#x}", attr.config1_or_bp_addr));
+        }
+        if attr.config2_or_bp_len != 0 {
+            terms.push_str(&format!(",config2={:#x}", attr.config2_or_bp_len));
+        }
+        if attr.config3 != 0 {
+            terms.push_str(&format!(",config3={:#x}", attr.config3));
+        }
+        format!("{}/{}/", pmu, terms)
+    });
+
+    let modifiers = format_modifiers(attr.settings);
+    if modifiers.is_empty() {
+        name
+    } else if name.ends_with('/') {
+        format!("{}{}", name, modifiers)
+    } else {
+        format!("{}:{}", name, modifiers)
+    }
+}
+
+fn format_cache_event(config: u64) -> Option<String> {
+    let (cache_name, _) = CACHES.iter().find(|(_, c)| *c as u64 == config & 0xff)?;
+    let (op_name, plural, _) = CACHE_OPS
+        .iter()
+        .find(|(_, _, op)| *op as u64 == (config >> 8) & 0xff)?;
+    match (config >> 16) & 0xff {
+        r if r == CacheOpResultId::Access as u64 => Some(format!("{}-{}", cache_name, plural)),
+        r if r == CacheOpResultId::Miss as u64 => {
+            Some(format!("{}-{}-misses", cache_name, op_name))
+        }
+        _ => None,
+    }
+}
+
+fn format_modifiers(settings: EventAttrFlags) -> String {
+    let mut modifiers = String::new();
+
+    let exclude_user = settings.contains(EventAttrFlags::EVENT_ATTR_EXCLUDE_USER);
+    let exclude_kernel = settings.contains(EventAttrFlags::EVENT_ATTR_EXCLUDE_KERNEL);
+    let exclude_hv = settings.contains(EventAttrFlags::EVENT_ATTR_EXCLUDE_HV);
+    if exclude_user || exclude_kernel || exclude_hv {
+        if !exclude_user {
+            modifiers.push('u');
+        }
+        if !exclude_kernel {
+            modifiers.push('k');
+        }
+        if !exclude_hv {
+            modifiers.push('h');
+        }
+    }
+
+    let exclude_host = settings.contains(EventAttrFlags::EVENT_ATTR_EXCLUDE_HOST);
+    let exclude_guest = settings.contains(EventAttrFlags::EVENT_ATTR_EXCLUDE_GUEST);
+    if exclude_host && !exclude_guest {
+        modifiers.push('G');
+    }
+    if exclude_guest && !exclude_host {
+        modifiers.push('H');
+    }
+
+    let precise = (settings.bits() >> 15) & 0x3;
+    for _ in 0..precise {
+        modifiers.push('p');
+    }
+
+    modifiers
+}
@@ -0,0 +1,167 @@
+//! Aggregates the callchains of a recording into folded stacks, the input format of
+//! flame graph tools (e.g., `flamegraph.pl` or `inferno-flamegraph`).
+//!
+//! Every line is one distinct stack, the frames from the root to the leaf separated by
+//! `;`, followed by a space and the weight of the stack:
+//!
+//! ```text
+//! server;main;handle_request;parse 1052
+//! server;main;handle_request;[libc.so.6] 87
+//! ```
+//!
+//! The name of the process is the root frame. Frames are symbolized with the
+//! `Symbolizer` (frames without a symbol are named after their binary) and looked up
+//! in the mappings the process had when the sample was taken (see `ProcessTracker`).
+//! Record with `PERF_SAMPLE_CALLCHAIN` (`perf record -g`), otherwise only the sampled
+//! instruction pointers are counted.
+//!
+//! # Example
+//! ```no_run
+//! use perfcnt::linux::folded::{FoldedStacks, Weight};
+//! use perfcnt::linux::perf_file::PerfFile;
+//!
+//! let pf = PerfFile::new(std::fs::read("perf.data").expect("Can not read perf.data"));
+//! let folded = FoldedStacks::from_perf_file(&pf, Weight::Period);
+//! folded.write(&mut std::io::stdout()).expect("Can not write folded stacks");
+//! ```
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use super::buildid::BuildIdResolver;
+use super::perf_event;
+use super::perf_file::PerfFile;
+use super::perf_format::{Event, EventData, SampleRecord};
+use super::process::ProcessTracker;
+use super::symbolize::Symbolizer;
+
+/// How much a sample adds to its stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weight {
+    /// Every sample counts 1.
+    Samples,
+    /// Every sample counts its period (`PERF_SAMPLE_PERIOD`), e.g., the number of
+    /// cycles for a frequency based cycles event. Samples without a period count 1.
+    Period,
+}
+
+/// Folded stacks of the samples of a recording.
+#[derive(Debug)]
+pub struct FoldedStacks {
+    weight: Weight,
+    tracker: ProcessTracker,
+    symbolizer: Symbolizer,
+    stacks: BTreeMap<String, u64>,
+}
+
+impl FoldedStacks {
+    pub fn new(weight: Weight) -> FoldedStacks {
+        FoldedStacks {
+            weight,
+            tracker: ProcessTracker::new(),
+            symbolizer: Symbolizer::new(),
+            stacks: BTreeMap::new(),
+        }
+    }
+
+    /// Folds all samples of `pf`, the binaries are located by the build ids of the
+    /// recording (see `BuildIdResolver`).
+    pub fn from_perf_file(pf: &PerfFile, weight: Weight) -> FoldedStacks {
+        let mut folded = FoldedStacks::new(weight);
+        folded
+            .symbolizer
+            .add_build_ids(&pf.get_build_ids(), &BuildIdResolver::new());
+        for event in pf.data() {
+            folded.process_event(&event);
+        }
+        folded
+    }
+
+    /// The `Symbolizer` used for the frames, e.g., to add the binaries of a
+    /// recording from another machine.
+    pub fn symbolizer_mut(&mut self) -> &mut Symbolizer {
+        &mut self.symbolizer
+    }
+
+    /// Follows the processes with `event` and adds it if it is a sample, events must
+    /// be passed in the order they were recorded.
+    pub fn process_event(&mut self, event: &Event) {
+        self.tracker.process_event(event);
+        if let EventData::Sample(ref sample) = event.data {
+            self.add_sample(sample);
+        }
+    }
+
+    /// Adds the callchain of `sample` (or its ip if it has no callchain).
+    ///
+    /// Samples without `PERF_SAMPLE_TIME` are resolved with the latest mappings.
+    pub fn add_sample(&mut self, sample: &SampleRecord) {
+        let ips = match (sample.ips.as_ref(), sample.ip.as_ref()) {
+            (Some(ips), _) => ips.as_slice(),
+            (None, Some(ip)) => std::slice::from_ref(ip),
+            (None, None) => return,
+        };
+        let pid = sample.ptid.as_ref().map_or(-1, |ptid| ptid.pid);
+        let time = sample.time.unwrap_or(u64::MAX);
+
+        let mut stack = frame_name(self.tracker.comm(pid, time).unwrap_or("[unknown]"));
+        // The callchain starts with the leaf:
+        for ip in ips
+            .iter()
+            .rev()
+            .filter(|ip| **ip < perf_event::PERF_CONTEXT_MAX)
+        {
+            let frame = match self.tracker.lookup(pid, time, *ip) {
+                Some(mapping) => {
+                    let resolved = self.symbolizer.resolve_mapping(mapping, *ip);
+                    match resolved.symbol {
+                        Some(symbol) => symbol,
+                        None => binary_frame(&resolved.binary),
+                    }
+                }
+                None => String::from("[unknown]"),
+            };
+            stack.push(';');
+            stack.push_str(&frame_name(&frame));
+        }
+
+        let weight = match self.weight {
+            Weight::Samples => 1,
+            Weight::Period => sample.period.unwrap_or(1),
+        };
+        *self.stacks.entry(stack).or_insert(0) += weight;
+    }
+
+    /// The folded stacks and their weights, sorted by stack.
+    pub fn stacks(&self) -> &BTreeMap<String, u64> {
+        &self.stacks
+    }
+
+    /// Writes the folded stacks, one per line.
+    pub fn write<W: io::Write>(&self, out: &mut W) -> Result<(), io::Error> {
+        for (stack, weight) in self.stacks.iter() {
+            writeln!(out, "{} {}", stack, weight)?;
+        }
+        Ok(())
+    }
+}
+
+/// `;` separates the frames and a line ends a stack, so they can't be in a name
+/// (demangled names contain e.g. `[u8; 4]`).
+fn frame_name(name: &str) -> String {
+    name.replace(';', ":").replace('\n', " ")
+}
+
+/// The frame of an address without a symbol, `[<file name>]` of its binary.
+fn binary_frame(binary: &str) -> String {
+    if binary.starts_with('[') {
+        // Already a pseudo file like `[vdso]` or `[kernel.kallsyms]`
+        return String::from(binary);
+    }
+    let name = Path::new(binary)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("unknown");
+    format!("[{}]", name)
+}
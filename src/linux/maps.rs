@@ -0,0 +1,162 @@
+//! The memory mappings of a process, to find out which file an address belongs to.
+//!
+//! A `Maps` is built from the `PERF_RECORD_MMAP` / `PERF_RECORD_MMAP2` records of a
+//! recording (the kernel also synthesizes them for the mappings that existed when
+//! the counter was opened), or read from `/proc/<pid>/maps` for a live process.
+//!
+//! # Example
+//! ```no_run
+//! use perfcnt::linux::maps::Maps;
+//!
+//! let maps = Maps::from_proc(std::process::id() as i32).expect("Can not read maps");
+//! if let Some(mapping) = maps.find(0x400000) {
+//!     println!("0x400000 is in {}", mapping.filename);
+//! }
+//! ```
+
+use std::fs;
+use std::io;
+
+use super::perf_format::{MMAP2Record, MMAPRecord};
+
+/// A mapped region of the address space.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    /// First address of the mapping.
+    pub start: u64,
+    /// First address after the mapping.
+    pub end: u64,
+    /// Offset in the file that is mapped at `start`.
+    pub pgoff: u64,
+    /// The mapped file, empty or a pseudo name (e.g., `[vdso]`) for anonymous memory.
+    pub filename: String,
+}
+
+impl Mapping {
+    pub fn new(start: u64, len: u64, pgoff: u64, filename: &str) -> Mapping {
+        Mapping {
+            start,
+            end: start + len,
+            pgoff,
+            filename: String::from(filename),
+        }
+    }
+
+    pub fn contains(&self, addr: u64) -> bool {
+        self.start <= addr && addr < self.end
+    }
+
+    /// The offset in the mapped file that `addr` refers to.
+    pub fn file_offset(&self, addr: u64) -> u64 {
+        addr - self.start + self.pgoff
+    }
+
+    /// Is this mapping backed by a file (rather than anonymous or special memory)?
+    pub fn is_file(&self) -> bool {
+        self.filename.starts_with('/')
+    }
+}
+
+impl<'a> From<&'a MMAPRecord> for Mapping {
+    fn from(record: &'a MMAPRecord) -> Mapping {
+        Mapping::new(record.addr, record.len, record.pgoff, &record.filename)
+    }
+}
+
+impl<'a> From<&'a MMAP2Record> for Mapping {
+    fn from(record: &'a MMAP2Record) -> Mapping {
+        Mapping::new(record.addr, record.len, record.pgoff, &record.filename)
+    }
+}
+
+/// The mappings of one process, sorted by address and without overlaps.
+#[derive(Debug, Clone, Default)]
+pub struct Maps {
+    mappings: Vec<Mapping>,
+}
+
+impl Maps {
+    pub fn new() -> Maps {
+        Default::default()
+    }
+
+    /// Reads the current mappings of process `pid`.
+    pub fn from_proc(pid: i32) -> Result<Maps, io::Error> {
+        let content = fs::read_to_string(format!("/proc/{}/maps", pid))?;
+        Ok(Maps::parse_proc_maps(&content))
+    }
+
+    /// Parses the content of a `/proc/<pid>/maps` file, lines that can not be
+    /// parsed are ignored.
+    pub fn parse_proc_maps(content: &str) -> Maps {
+        let mut maps = Maps::new();
+        for line in content.lines() {
+            // 55d0c3e00000-55d0c3e02000 r-xp 00002000 08:01 1234    /usr/bin/foo
+            let mut fields = line.splitn(6, ' ');
+            let range = fields.next().unwrap_or("");
+            let _perms = fields.next();
+            let pgoff = fields.next().and_then(|o| u64::from_str_radix(o, 16).ok());
+            let _dev = fields.next();
+            let _inode = fields.next();
+            let filename = fields.next().unwrap_or("").trim();
+
+            let mut bounds = range.splitn(2, '-');
+            let start = bounds.next().and_then(|s| u64::from_str_radix(s, 16).ok());
+            let end = bounds.next().and_then(|e| u64::from_str_radix(e, 16).ok());
+            if let (Some(start), Some(end), Some(pgoff)) = (start, end, pgoff) {
+                if start < end {
+                    maps.insert(Mapping::new(start, end - start, pgoff, filename));
+                }
+            }
+        }
+        maps
+    }
+
+    /// Adds `mapping`, it replaces whatever was mapped in its range before (like
+    /// `mmap` with `MAP_FIXED` does).
+    pub fn insert(&mut self, mapping: Mapping) {
+        let mut mappings = Vec::with_capacity(self.mappings.len() + 2);
+        for m in self.mappings.drain(..) {
+            if m.end <= mapping.start || m.start >= mapping.end {
+                mappings.push(m);
+                continue;
+            }
+            if m.start < mapping.start {
+                mappings.push(Mapping {
+                    end: mapping.start,
+                    ..m.clone()
+                });
+            }
+            if m.end > mapping.end {
+                mappings.push(Mapping {
+                    start: mapping.end,
+                    pgoff: m.file_offset(mapping.end),
+                    ..m
+                });
+            }
+        }
+        mappings.push(mapping);
+        mappings.sort_by_key(|m| m.start);
+        self.mappings = mappings;
+    }
+
+    /// The mapping that contains `addr`.
+    pub fn find(&self, addr: u64) -> Option<&Mapping> {
+        let idx = self.mappings.partition_point(|m| m.start <= addr);
+        idx.checked_sub(1)
+            .map(|i| &self.mappings[i])
+            .filter(|m| m.contains(addr))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Mapping> {
+        self.mappings.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.mappings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mappings.is_empty()
+    }
+}
@@ -1,14 +1,17 @@
 //! A wrapper around perf_event open (http://lxr.free-electrons.com/source/tools/perf/design.txt)
 
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fs::File;
 use std::io;
 use std::io::{Error, Read};
 use std::mem;
-use std::os::unix::io::FromRawFd;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
 use std::ptr;
 use std::slice;
 use std::str;
+use std::sync::atomic::{compiler_fence, Ordering};
 
 use libc::{pid_t, strlen, MAP_SHARED};
 use mmap;
@@ -18,16 +21,34 @@ mod hw_breakpoint;
 #[allow(dead_code, non_camel_case_types)]
 mod perf_event;
 
+pub mod amd;
+pub mod arm;
+pub mod buildid;
+pub mod command;
+pub mod error;
+pub mod event_str;
+pub mod folded;
+pub mod maps;
 pub mod parser;
+pub mod percpu;
 pub mod perf_file;
 pub mod perf_format;
-
-use self::perf_format::{EventAttrFlags, ReadFormatFlags, SampleFormatFlags};
+pub mod pmu;
+pub mod probe;
+pub mod process;
+pub mod regs;
+pub mod symbolize;
+pub mod unwind;
+
+use self::error::PerfError;
+use self::perf_format::{
+    BranchEntry, BranchSampleType, EventAttrFlags, ReadFormatFlags, SampleFormatFlags,
+};
 
 use crate::AbstractPerfCounter;
 
 fn perf_event_open(
-    hw_event: &perf_format::EventAttr,
+    hw_event: &mut perf_format::EventAttr,
     pid: perf_event::__kernel_pid_t,
     cpu: ::libc::c_int,
     group_fd: ::libc::c_int,
@@ -36,7 +57,7 @@ fn perf_event_open(
     unsafe {
         libc::syscall(
             libc::SYS_perf_event_open,
-            hw_event as *const perf_format::EventAttr as usize,
+            hw_event as *mut perf_format::EventAttr as usize,
             pid,
             cpu,
             group_fd,
@@ -49,12 +70,27 @@ fn ioctl(fd: ::libc::c_int, request: u64, value: ::libc::c_int) -> isize {
     unsafe { libc::ioctl(fd, request, value) as isize }
 }
 
+/// Retrieves the unique id the kernel assigned to the event behind `fd`.
+fn ioctl_id(fd: ::libc::c_int) -> Result<u64, io::Error> {
+    let mut id: u64 = 0;
+    let ret = unsafe { libc::ioctl(fd, perf_event::PERF_EVENT_IOC_ID, &mut id as *mut u64) };
+    if ret == -1 {
+        return Err(Error::last_os_error());
+    }
+    Ok(id)
+}
+
+/// Places where tracefs is usually mounted, in order of preference.
+const TRACEFS_ROOTS: [&str; 2] = ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"];
+
+#[derive(Clone)]
 pub struct PerfCounterBuilderLinux {
     group: isize,
     pid: pid_t,
     cpu: isize,
     flags: i32,
     attrs: perf_format::EventAttr,
+    cgroup: Option<PathBuf>,
 }
 
 impl Default for PerfCounterBuilderLinux {
@@ -65,6 +101,7 @@ impl Default for PerfCounterBuilderLinux {
             cpu: -1,
             flags: 0,
             attrs: Default::default(),
+            cgroup: None,
         }
     }
 }
@@ -195,6 +232,31 @@ pub enum CacheOpResultId {
     Miss = perf_event::PERF_COUNT_HW_CACHE_RESULT_MISS as isize,
 }
 
+/// The kind of memory access that triggers a hardware breakpoint.
+#[derive(Debug, Clone, Copy)]
+pub enum BreakpointType {
+    /// Count reads of the address (not supported on x86, use ReadWrite).
+    Read = hw_breakpoint::HW_BREAKPOINT_R as isize,
+
+    /// Count writes to the address.
+    Write = hw_breakpoint::HW_BREAKPOINT_W as isize,
+
+    /// Count reads and writes of the address.
+    ReadWrite = hw_breakpoint::HW_BREAKPOINT_RW as isize,
+
+    /// Count executions of the instruction at the address.
+    Execute = hw_breakpoint::HW_BREAKPOINT_X as isize,
+}
+
+/// Number of bytes watched by a hardware breakpoint.
+#[derive(Debug, Clone, Copy)]
+pub enum BreakpointLen {
+    One = hw_breakpoint::HW_BREAKPOINT_LEN_1 as isize,
+    Two = hw_breakpoint::HW_BREAKPOINT_LEN_2 as isize,
+    Four = hw_breakpoint::HW_BREAKPOINT_LEN_4 as isize,
+    Eight = hw_breakpoint::HW_BREAKPOINT_LEN_8 as isize,
+}
+
 impl PerfCounterBuilderLinux {
     /// Instantiate a generic performance counter for hardware events as defined by the Linux interface.
     pub fn from_hardware_event(event: HardwareEventType) -> PerfCounterBuilderLinux {
@@ -228,25 +290,166 @@ impl PerfCounterBuilderLinux {
         pc
     }
 
-    //pub fn from_breakpoint_event() -> PerfCounterBuilderLinux {
-    // NYI
-    //}
+    /// Instantiate a counter for a kernel tracepoint, e.g., `sched:sched_switch`.
+    ///
+    /// The id of the tracepoint is looked up in tracefs, which is usually mounted at
+    /// `/sys/kernel/tracing` or (on older systems) `/sys/kernel/debug/tracing`.
+    pub fn from_tracepoint(name: &str) -> Result<PerfCounterBuilderLinux, io::Error> {
+        let root = TRACEFS_ROOTS
+            .iter()
+            .map(Path::new)
+            .find(|root| root.join("events").is_dir())
+            .unwrap_or_else(|| Path::new(TRACEFS_ROOTS[0]));
+        PerfCounterBuilderLinux::from_tracepoint_in(name, root)
+    }
+
+    /// Instantiate a counter for a kernel tracepoint, using the tracefs mounted at `tracefs_root`.
+    ///
+    /// `name` is the subsystem and the event separated by a colon (e.g. `syscalls:sys_enter_read`).
+    pub fn from_tracepoint_in(
+        name: &str,
+        tracefs_root: &Path,
+    ) -> Result<PerfCounterBuilderLinux, io::Error> {
+        let mut parts = name.splitn(2, [':', '/']);
+        let (subsystem, event) = match (parts.next(), parts.next()) {
+            (Some(s), Some(e)) if !s.is_empty() && !e.is_empty() => (s, e),
+            _ => {
+                return Err(Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "Invalid tracepoint '{}', expected <subsystem>:<event>",
+                        name
+                    ),
+                ))
+            }
+        };
+
+        let event_dir = tracefs_root.join("events").join(subsystem).join(event);
+        if !event_dir.is_dir() {
+            return Err(Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "Tracepoint '{}' does not exist ({} not found)",
+                    name,
+                    event_dir.display()
+                ),
+            ));
+        }
+
+        let id_file = event_dir.join("id");
+        let id: u64 = std::fs::read_to_string(&id_file)
+            .map_err(|e| {
+                Error::new(
+                    e.kind(),
+                    format!("Can not read {}: {}", id_file.display(), e),
+                )
+            })?
+            .trim()
+            .parse()
+            .map_err(|_| {
+                Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid tracepoint id in {}", id_file.display()),
+                )
+            })?;
+
+        let mut pc: PerfCounterBuilderLinux = Default::default();
+        pc.attrs.attr_type = perf_event::PERF_TYPE_TRACEPOINT;
+        pc.attrs.config = id;
+        Ok(pc)
+    }
+
+    /// Instantiate a counter from an event string as used by `perf`, e.g., `cycles:u`,
+    /// `L1-dcache-load-misses`, `r01c2:pp` or `cpu/event=0x3c,umask=0x0/k`.
+    ///
+    /// See `event_str` for the supported syntax, use `PerfCounterGroupBuilder::from_event_str`
+    /// for groups.
+    pub fn from_event_str(event: &str) -> Result<PerfCounterBuilderLinux, io::Error> {
+        event_str::parse_event(event)
+    }
+
+    /// Instantiate a counter for the event described by `attr` (e.g., from `pmu::PmuRegistry`).
+    ///
+    /// Only the fields that select the event (type and the config fields) are taken
+    /// from `attr`, everything else is configured with the builder as usual.
+    pub fn from_event_attr(attr: &perf_format::EventAttr) -> PerfCounterBuilderLinux {
+        let mut pc: PerfCounterBuilderLinux = Default::default();
+
+        pc.attrs.attr_type = attr.attr_type;
+        pc.attrs.config = attr.config;
+        pc.attrs.config1_or_bp_addr = attr.config1_or_bp_addr;
+        pc.attrs.config2_or_bp_len = attr.config2_or_bp_len;
+        pc.attrs.config3 = attr.config3;
+        pc
+    }
+
+    /// Instantiate a counter for a hardware breakpoint (or watchpoint) at `addr`.
+    ///
+    /// For `BreakpointType::Execute` the kernel expects the length to be
+    /// `sizeof(long)`, so `len` is ignored in that case.
+    pub fn from_breakpoint(
+        addr: u64,
+        bp_type: BreakpointType,
+        len: BreakpointLen,
+    ) -> PerfCounterBuilderLinux {
+        let mut pc: PerfCounterBuilderLinux = Default::default();
+
+        pc.attrs.attr_type = perf_event::PERF_TYPE_BREAKPOINT;
+        pc.attrs.bp_type = bp_type as u32;
+        pc.attrs.config1_or_bp_addr = addr;
+        pc.attrs.config2_or_bp_len = match bp_type {
+            BreakpointType::Execute => mem::size_of::<libc::c_long>() as u64,
+            _ => len as u64,
+        };
+        pc
+    }
 
     /// Instantiate a H/W performance counter using a hardware event as described in Intels SDM.
+    ///
+    /// Returns an error for events that can not be expressed as a raw core event
+    /// (uncore events or events that need an unknown MSR).
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-    pub fn from_intel_event_description(counter: &x86::perfcnt::intel::EventDescription) -> PerfCounterBuilderLinux {
-        use x86::perfcnt::intel::Tuple;
+    pub fn from_intel_event_description(
+        counter: &x86::perfcnt::intel::EventDescription,
+    ) -> Result<PerfCounterBuilderLinux, io::Error> {
+        use x86::perfcnt::intel::{Counter, MSRIndex, PebsType, Tuple};
+        let unsupported = |reason: &str| {
+            Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Can not encode {}: {}", counter.event_name, reason),
+            )
+        };
+
+        if counter.uncore {
+            return Err(unsupported(
+                "uncore events have to be opened through their PMU (see pmu::PmuRegistry)",
+            ));
+        }
+
         let mut pc: PerfCounterBuilderLinux = Default::default();
         let mut config: u64 = 0;
 
-        match counter.event_code {
-            Tuple::One(code) => config |= (code as u64) << 0,
-            Tuple::Two(_, _) => unreachable!(), // NYI
+        // Events with two codes (or umasks) are the offcore response events, one for
+        // each of the MSR_OFFCORE_RSP_x registers. Linux expects the first encoding
+        // and picks a free MSR itself.
+        let event_code = match counter.event_code {
+            Tuple::One(code) => code,
+            Tuple::Two(code, _) => code,
         };
-        match counter.umask {
-            Tuple::One(code) => config |= (code as u64) << 8,
-            Tuple::Two(_, _) => unreachable!(), // NYI
+        let umask = match counter.umask {
+            Tuple::One(code) => code,
+            Tuple::Two(code, _) => code,
         };
+
+        // Events that only exist on fixed counters use pseudo encodings in Linux:
+        let (event_code, umask) = match (counter.counter, event_code, umask) {
+            (Counter::Fixed(_), 0x00, 0x01) => (0xc0, 0x00), // INST_RETIRED.ANY
+            (Counter::Fixed(_), 0x00, 0x02) => (0x3c, 0x00), // CPU_CLK_UNHALTED.THREAD
+            (_, code, umask) => (code, umask),
+        };
+
+        config |= event_code as u64;
+        config |= (umask as u64) << 8;
         config |= (counter.counter_mask as u64) << 24;
 
         if counter.edge_detect {
@@ -259,8 +462,54 @@ impl PerfCounterBuilderLinux {
             config |= 1 << 23;
         }
 
+        let msr = match counter.msr_index {
+            MSRIndex::None => None,
+            MSRIndex::One(msr) => Some(msr),
+            MSRIndex::Two(msr, _) => Some(msr),
+        };
+        match msr {
+            None | Some(0) => {}
+            // MSR_OFFCORE_RSP_0/1, MSR_PEBS_LD_LAT_THRESHOLD and MSR_PEBS_FRONTEND
+            // are programmed by the kernel from config1:
+            Some(0x1a6) | Some(0x1a7) | Some(0x3f6) | Some(0x3f7) => {
+                pc.attrs.config1_or_bp_addr = counter.msr_value;
+            }
+            Some(msr) => {
+                return Err(unsupported(&format!("MSR {:#x} is not supported", msr)));
+            }
+        }
+
+        if counter.pebs == PebsType::PebsOnly {
+            pc.set_ip_sample_constant_skid();
+        }
+        if counter.taken_alone {
+            pc.exclusive();
+        }
+
         pc.attrs.attr_type = perf_event::PERF_TYPE_RAW;
         pc.attrs.config = config;
+        Ok(pc)
+    }
+
+    /// Instantiate a H/W performance counter for a raw core event of an AMD processor.
+    ///
+    /// See `amd::ZEN_EVENTS` for a table of common events.
+    pub fn from_amd_event(event: &amd::AmdEvent) -> Result<PerfCounterBuilderLinux, io::Error> {
+        let mut pc: PerfCounterBuilderLinux = Default::default();
+
+        pc.attrs.attr_type = perf_event::PERF_TYPE_RAW;
+        pc.attrs.config = event.config()?;
+        Ok(pc)
+    }
+
+    /// Instantiate a H/W performance counter for an architected common event of an Arm core.
+    ///
+    /// See `arm::ARM_PMUV3_EVENTS` for the table of events.
+    pub fn from_arm_event(event: &arm::ArmPmuV3Event) -> PerfCounterBuilderLinux {
+        let mut pc: PerfCounterBuilderLinux = Default::default();
+
+        pc.attrs.attr_type = perf_event::PERF_TYPE_RAW;
+        pc.attrs.config = event.config();
         pc
     }
 
@@ -290,6 +539,23 @@ impl PerfCounterBuilderLinux {
         self
     }
 
+    /// Only count events of tasks that belong to the cgroup at `path`.
+    ///
+    /// Relative paths are resolved against the perf_event hierarchy for cgroup v1
+    /// (`/sys/fs/cgroup/perf_event`) if it exists, otherwise against the unified
+    /// hierarchy (`/sys/fs/cgroup/unified` in the hybrid layout, `/sys/fs/cgroup`).
+    ///
+    /// The kernel only supports this per CPU, so the counter must be pinned
+    /// with `on_cpu` (see also `percpu::PerCpuCounter`).
+    /// The cgroup directory is kept open for the lifetime of the counter.
+    pub fn for_cgroup<'a, P: AsRef<Path>>(
+        &'a mut self,
+        path: P,
+    ) -> &'a mut PerfCounterBuilderLinux {
+        self.cgroup = Some(path.as_ref().to_path_buf());
+        self.set_flag_pid_cgroup()
+    }
+
     /// Add a sample period.
     pub fn set_sample_period<'a>(&'a mut self, period: u64) -> &'a mut PerfCounterBuilderLinux {
         self.attrs.sample_period_freq = period;
@@ -372,6 +638,22 @@ impl PerfCounterBuilderLinux {
         self
     }
 
+    /// The counter does not count while the CPU runs the host (only count in guests).
+    pub fn exclude_host<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .settings
+            .insert(EventAttrFlags::EVENT_ATTR_EXCLUDE_HOST);
+        self
+    }
+
+    /// The counter does not count while the CPU runs a guest (only count in the host).
+    pub fn exclude_guest<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .settings
+            .insert(EventAttrFlags::EVENT_ATTR_EXCLUDE_GUEST);
+        self
+    }
+
     /// Enables recording of exec mmap events.
     pub fn enable_mmap<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
         self.attrs.settings.insert(EventAttrFlags::EVENT_ATTR_MMAP);
@@ -454,6 +736,148 @@ impl PerfCounterBuilderLinux {
         self
     }
 
+    /// Mmap records include the inode data (or the build id with `enable_build_id`).
+    pub fn enable_mmap2<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs.settings.insert(EventAttrFlags::EVENT_ATTR_MMAP2);
+        self
+    }
+
+    /// Comm records caused by an exec are flagged as such.
+    pub fn enable_comm_exec<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .settings
+            .insert(EventAttrFlags::EVENT_ATTR_COMM_EXEC);
+        self
+    }
+
+    /// Time stamps are taken from `clock_id` (e.g., `libc::CLOCK_MONOTONIC_RAW`).
+    pub fn use_clock_id<'a>(&'a mut self, clock_id: i32) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .settings
+            .insert(EventAttrFlags::EVENT_ATTR_USE_CLOCKID);
+        self.attrs.clock_id = clock_id;
+        self
+    }
+
+    /// Context switches are included in the ring buffer.
+    pub fn enable_context_switch<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .settings
+            .insert(EventAttrFlags::EVENT_ATTR_CONTEXT_SWITCH);
+        self
+    }
+
+    /// The ring buffer is written from the end to the beginning (an overwritable buffer).
+    pub fn write_backward<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .settings
+            .insert(EventAttrFlags::EVENT_ATTR_WRITE_BACKWARD);
+        self
+    }
+
+    /// Namespace records of new tasks are included in the ring buffer.
+    pub fn enable_namespaces<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .settings
+            .insert(EventAttrFlags::EVENT_ATTR_NAMESPACES);
+        self
+    }
+
+    /// Registration of kernel symbols (e.g., JIT-ed BPF programs) is included in the ring buffer.
+    pub fn enable_ksymbol<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .settings
+            .insert(EventAttrFlags::EVENT_ATTR_KSYMBOL);
+        self
+    }
+
+    /// Loading and unloading of BPF programs is included in the ring buffer.
+    pub fn enable_bpf_event<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .settings
+            .insert(EventAttrFlags::EVENT_ATTR_BPF_EVENT);
+        self
+    }
+
+    /// The samples of this event go to the AUX area of the group leader.
+    pub fn aux_output<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .settings
+            .insert(EventAttrFlags::EVENT_ATTR_AUX_OUTPUT);
+        self
+    }
+
+    /// Creation of cgroups is included in the ring buffer.
+    pub fn enable_cgroup<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .settings
+            .insert(EventAttrFlags::EVENT_ATTR_CGROUP);
+        self
+    }
+
+    /// Modifications of kernel text are included in the ring buffer.
+    pub fn enable_text_poke<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .settings
+            .insert(EventAttrFlags::EVENT_ATTR_TEXT_POKE);
+        self
+    }
+
+    /// Mmap2 records carry the build id instead of the inode data.
+    pub fn enable_build_id<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .settings
+            .insert(EventAttrFlags::EVENT_ATTR_BUILD_ID);
+        self
+    }
+
+    /// With `inherit`, only threads (clone with CLONE_THREAD) inherit the counter.
+    pub fn inherit_thread<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .settings
+            .insert(EventAttrFlags::EVENT_ATTR_INHERIT_THREAD);
+        self
+    }
+
+    /// The counter is removed from the task on exec.
+    pub fn remove_on_exec<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .settings
+            .insert(EventAttrFlags::EVENT_ATTR_REMOVE_ON_EXEC);
+        self
+    }
+
+    /// Send a synchronous SIGTRAP to the task when the event overflows, `sig_data` is
+    /// passed in `si_perf_data` (requires `remove_on_exec`).
+    pub fn sigtrap<'a>(&'a mut self, sig_data: u64) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .settings
+            .insert(EventAttrFlags::EVENT_ATTR_SIGTRAP);
+        self.attrs.sig_data = sig_data;
+        self
+    }
+
+    /// Maximum number of frames in a sampled callchain.
+    pub fn set_sample_max_stack<'a>(
+        &'a mut self,
+        max_stack: u16,
+    ) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs.sample_max_stack = max_stack;
+        self
+    }
+
+    /// Number of bytes of AUX data to include in each sample.
+    pub fn set_aux_sample_size<'a>(&'a mut self, size: u32) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs.aux_sample_size = size;
+        self
+    }
+
+    /// Sets the PMU specific `config3`.
+    pub fn set_config3<'a>(&'a mut self, config3: u64) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs.config3 = config3;
+        self
+    }
+
     /// Adds the 64-bit time_enabled field.  This can be used to calculate estimated totals if the PMU is overcommitted
     /// and multiplexing is happening.
     pub fn enable_read_format_time_enabled<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
@@ -472,6 +896,13 @@ impl PerfCounterBuilderLinux {
         self
     }
 
+    /// Adds both time_enabled and time_running, `read_scaled` needs them to extrapolate
+    /// the value of a multiplexed event (`finish` adds them to every counter).
+    pub fn enable_read_format_scaling<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
+        self.enable_read_format_time_enabled()
+            .enable_read_format_time_running()
+    }
+
     /// Adds a 64-bit unique value that corresponds to the event group.
     pub fn enable_read_format_id<'a>(&'a mut self) -> &'a mut PerfCounterBuilderLinux {
         self.attrs.read_format.insert(ReadFormatFlags::FORMAT_ID);
@@ -561,10 +992,36 @@ impl PerfCounterBuilderLinux {
         self
     }
 
+    /// Record the branch stack (LBR), see `set_branch_sample_type` for which branches.
+    ///
+    /// If no branch type was selected yet, all branches are recorded.
     pub fn enable_sampling_branch_stack<'a>(&'a mut self) -> &'a PerfCounterBuilderLinux {
         self.attrs
             .sample_type
             .insert(SampleFormatFlags::PERF_SAMPLE_BRANCH_STACK);
+        // The kernel rejects a branch stack without any branch type:
+        let types =
+            self.attrs.branch_sample_type - self.attrs.branch_sample_type.privilege_levels();
+        if types.is_empty() {
+            self.attrs
+                .branch_sample_type
+                .insert(BranchSampleType::PERF_SAMPLE_BRANCH_ANY);
+        }
+        self
+    }
+
+    /// Record the branch stack, filtered by `branch_types`.
+    ///
+    /// Without any of the privilege level flags (user, kernel, hv) they follow the
+    /// `exclude_*` settings of the counter.
+    pub fn set_branch_sample_type<'a>(
+        &'a mut self,
+        branch_types: BranchSampleType,
+    ) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .sample_type
+            .insert(SampleFormatFlags::PERF_SAMPLE_BRANCH_STACK);
+        self.attrs.branch_sample_type = branch_types;
         self
     }
 
@@ -575,6 +1032,31 @@ impl PerfCounterBuilderLinux {
         self
     }
 
+    /// Record the user mode registers `regs` (e.g., `&[Reg::Ip, Reg::Sp, Reg::Bp]`) in samples.
+    pub fn sample_user_regs<'a, R: regs::Register>(
+        &'a mut self,
+        regs: &[R],
+    ) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .sample_type
+            .insert(SampleFormatFlags::PERF_SAMPLE_REGS_USER);
+        self.attrs.sample_regs_user = regs::regs_mask(regs);
+        self
+    }
+
+    /// Record the registers `regs` at the time of the overflow interrupt in samples
+    /// (these can be kernel mode registers).
+    pub fn sample_intr_regs<'a, R: regs::Register>(
+        &'a mut self,
+        regs: &[R],
+    ) -> &'a mut PerfCounterBuilderLinux {
+        self.attrs
+            .sample_type
+            .insert(SampleFormatFlags::PERF_SAMPLE_REGS_INTR);
+        self.attrs.sample_regs_intr = regs::regs_mask(regs);
+        self
+    }
+
     pub fn enable_sampling_stack_user<'a>(&'a mut self) -> &'a PerfCounterBuilderLinux {
         self.attrs
             .sample_type
@@ -634,46 +1116,92 @@ impl PerfCounterBuilderLinux {
         self
     }
 
-    pub fn finish_sampling_counter(&self) -> Result<PerfCounter, io::Error> {
-        let fd = perf_event_open(
-            &self.attrs,
-            self.pid,
-            self.cpu as i32,
-            self.group as i32,
-            self.flags,
-        ) as ::libc::c_int;
-        if fd < 0 {
-            return Err(Error::from_raw_os_error(-fd));
-        }
+    pub fn finish_sampling_counter(&self) -> Result<PerfCounter, PerfError> {
+        self.open()
+    }
 
-        Ok(PerfCounter {
-            fd,
-            file: unsafe { File::from_raw_fd(fd) },
-            attributes: self.attrs,
-        })
+    /// The attributes the counter will be opened with.
+    pub fn attributes(&self) -> &perf_format::EventAttr {
+        &self.attrs
     }
 
     /// Instantiate the performance counter.
-    pub fn finish(&self) -> Result<PerfCounter, io::Error> {
+    ///
+    /// The counter always reads time_enabled and time_running (see `read_scaled`),
+    /// `read` still returns only the value.
+    ///
+    /// If the kernel refuses the event, the error says why (see `error::PerfError`).
+    pub fn finish(&self) -> Result<PerfCounter, PerfError> {
+        self.open()
+    }
+
+    fn open(&self) -> Result<PerfCounter, PerfError> {
+        let (pid, cgroup) = match self.cgroup {
+            Some(ref path) => {
+                if self.cpu == -1 {
+                    return Err(PerfError::Io(Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "Counting for a cgroup requires a CPU, use on_cpu",
+                    )));
+                }
+                let dir = File::open(resolve_cgroup_path(path))?;
+                (dir.as_raw_fd(), Some(dir))
+            }
+            None => (self.pid, None),
+        };
+
+        // Announce the full size. An older kernel accepts it as long as the fields it
+        // does not know are zero, otherwise it fails with E2BIG and writes back the size
+        // it supports (the error lists the fields that are too new):
+        let mut attrs = self.attrs;
+        attrs.size = mem::size_of::<perf_format::EventAttr>() as u32;
+        // Reads are decoded with the read_format, so the timing costs nothing but 16 bytes:
+        attrs.read_format.insert(
+            ReadFormatFlags::FORMAT_TOTAL_TIME_ENABLED | ReadFormatFlags::FORMAT_TOTAL_TIME_RUNNING,
+        );
         let fd = perf_event_open(
-            &self.attrs,
-            self.pid,
+            &mut attrs,
+            pid,
             self.cpu as i32,
             self.group as i32,
             self.flags,
         ) as ::libc::c_int;
+        let errno = Error::last_os_error().raw_os_error().unwrap_or(0);
+
         if fd < 0 {
-            return Err(Error::from_raw_os_error(-fd));
+            let system_wide = self.pid == -1 || cgroup.is_some();
+            return Err(PerfError::from_errno(errno, &attrs, system_wide));
         }
 
         Ok(PerfCounter {
             fd,
             file: unsafe { File::from_raw_fd(fd) },
-            attributes: self.attrs,
+            attributes: attrs,
+            cgroup,
         })
     }
 }
 
+/// Finds the directory of a cgroup, relative paths are looked up in the
+/// hierarchy that has the perf_event controller.
+fn resolve_cgroup_path(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+
+    let v1 = Path::new("/sys/fs/cgroup/perf_event");
+    // The cgroup v2 hierarchy of the hybrid layout (v1 controllers next to it):
+    let hybrid = Path::new("/sys/fs/cgroup/unified");
+    let root = if v1.is_dir() {
+        v1
+    } else if hybrid.is_dir() {
+        hybrid
+    } else {
+        Path::new("/sys/fs/cgroup")
+    };
+    root.join(path)
+}
+
 #[repr(C)]
 #[derive(Default, Debug)]
 pub struct FileReadFormat {
@@ -687,22 +1215,6 @@ pub struct FileReadFormat {
     pub id: u64,
 }
 
-impl FileReadFormat {
-    unsafe fn copy_from_raw_ptr(ptr: *const u8) -> FileReadFormat {
-        let value: u64 = read(ptr, 0);
-        let time_enabled: u64 = read(ptr, 8);
-        let time_running: u64 = read(ptr, 16);
-        let id: u64 = read(ptr, 24);
-
-        FileReadFormat {
-            value,
-            time_enabled,
-            time_running,
-            id,
-        }
-    }
-}
-
 #[repr(C)]
 pub struct MMAPPage {
     /// version number of this structure
@@ -746,19 +1258,48 @@ pub struct PerfCounter {
     fd: ::libc::c_int,
     file: File,
     attributes: perf_format::EventAttr,
+    /// The cgroup directory, for counters created with `for_cgroup`.
+    #[allow(dead_code)]
+    cgroup: Option<File>,
 }
 
 impl PerfCounter {
+    /// The attributes the kernel accepted, `size` is what the kernel supports.
+    pub fn attributes(&self) -> &perf_format::EventAttr {
+        &self.attributes
+    }
+
     /// Read the file descriptor and parse the return format.
-    pub fn read_fd(&mut self) -> Result<FileReadFormat, io::Error> {
-        unsafe {
-            let mut value: FileReadFormat = Default::default();
-            let ptr = mem::transmute::<&mut FileReadFormat, &mut u8>(&mut value);
-            let slice = slice::from_raw_parts_mut::<u8>(ptr, mem::size_of::<FileReadFormat>());
-            self.file.read_exact(slice)?;
-            Ok(value)
+    ///
+    /// The layout of the data depends on the `read_format` the counter was
+    /// created with, it is decoded accordingly.
+    pub fn read_fd(&mut self) -> Result<perf_format::ReadFormat, io::Error> {
+        let mut buf = [0u8; 4096];
+        let len = self.file.read(&mut buf)?;
+        match parser::parse_read_format(&buf[..len], self.attributes.read_format) {
+            Ok((_, rf)) => Ok(rf),
+            Err(_) => Err(Error::new(
+                io::ErrorKind::InvalidData,
+                "Unable to parse read format",
+            )),
         }
     }
+
+    /// Read the counter and extrapolate the value in case the event was multiplexed.
+    ///
+    /// Check `ScaledValue::never_scheduled` before trusting the result:
+    /// an event that never got a hardware counter has no meaningful value.
+    pub fn read_scaled(&mut self) -> Result<perf_format::ScaledValue, io::Error> {
+        self.read_fd()?
+            .scaled_values()
+            .and_then(|values| values.into_iter().next())
+            .ok_or_else(|| {
+                Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Counter was read without time_enabled and time_running",
+                )
+            })
+    }
 }
 
 impl<'a> AbstractPerfCounter for PerfCounter {
@@ -787,8 +1328,321 @@ impl<'a> AbstractPerfCounter for PerfCounter {
     }
 
     fn read(&mut self) -> Result<u64, io::Error> {
-        let value: FileReadFormat = self.read_fd()?;
-        return Ok(value.value);
+        let value: perf_format::ReadFormat = self.read_fd()?;
+        value
+            .value()
+            .ok_or_else(|| Error::new(io::ErrorKind::InvalidData, "Read returned no value"))
+    }
+}
+
+/// Collects a leader and its members in order to open them as one counter group.
+///
+/// The leader decides when the group is scheduled on the PMU, so all members
+/// are always measured during exactly the same time.
+#[derive(Clone)]
+pub struct PerfCounterGroupBuilder {
+    leader: PerfCounterBuilderLinux,
+    members: Vec<PerfCounterBuilderLinux>,
+}
+
+impl PerfCounterGroupBuilder {
+    /// Start a new group with `leader` as the group leader.
+    pub fn new(leader: PerfCounterBuilderLinux) -> PerfCounterGroupBuilder {
+        PerfCounterGroupBuilder {
+            leader,
+            members: Vec::new(),
+        }
+    }
+
+    /// Instantiate a group from an event string as used by `perf`, e.g., `{cycles,instructions}:u`.
+    pub fn from_event_str(group: &str) -> Result<PerfCounterGroupBuilder, io::Error> {
+        event_str::parse_group(group)
+    }
+
+    /// Add another event to the group.
+    pub fn add_member<'a>(
+        &'a mut self,
+        member: PerfCounterBuilderLinux,
+    ) -> &'a mut PerfCounterGroupBuilder {
+        self.members.push(member);
+        self
+    }
+
+    /// The builder of the group leader.
+    pub fn leader(&self) -> &PerfCounterBuilderLinux {
+        &self.leader
+    }
+
+    /// The builders of the group members, in the order they were added.
+    pub fn members(&self) -> &[PerfCounterBuilderLinux] {
+        &self.members
+    }
+
+    /// Open the leader and all members.
+    ///
+    /// The group starts out disabled, use `start` to enable all counters at once.
+    pub fn finish(&self) -> Result<PerfCounterGroup, PerfError> {
+        let mut leader = self.leader.clone();
+        leader
+            .disable()
+            .enable_read_format_group()
+            .enable_read_format_id()
+            .set_group(-1);
+        let leader = leader.finish()?;
+        let leader_id = ioctl_id(leader.fd)?;
+
+        let mut members = Vec::with_capacity(self.members.len());
+        let mut ids = Vec::with_capacity(self.members.len() + 1);
+        ids.push((leader_id, leader.attributes));
+        for member in self.members.iter() {
+            let mut member = member.clone();
+            member
+                .enable_read_format_group()
+                .enable_read_format_id()
+                .set_group(leader.fd as isize);
+            // Members follow the leader, they must not be disabled on their own:
+            member
+                .attrs
+                .settings
+                .remove(EventAttrFlags::EVENT_ATTR_DISABLED);
+            let member = member.finish()?;
+            ids.push((ioctl_id(member.fd)?, member.attributes));
+            members.push(member);
+        }
+
+        Ok(PerfCounterGroup {
+            leader,
+            members,
+            ids,
+        })
+    }
+}
+
+/// The value of a single event within a group read.
+#[derive(Debug, Clone, Copy)]
+pub struct GroupValue {
+    /// The event that was counted.
+    pub event: perf_format::EventAttr,
+    /// The unique id the kernel assigned to the event.
+    pub id: u64,
+    /// The counter value.
+    pub value: u64,
+}
+
+/// The result of reading all counters of a group at once.
+#[derive(Debug)]
+pub struct GroupReadFormat {
+    /// if PERF_FORMAT_TOTAL_TIME_ENABLED
+    pub time_enabled: Option<u64>,
+    /// if PERF_FORMAT_TOTAL_TIME_RUNNING
+    pub time_running: Option<u64>,
+    /// One entry per event, the leader comes first.
+    pub values: Vec<GroupValue>,
+}
+
+/// A set of counters that are enabled, disabled, reset and read as one unit.
+pub struct PerfCounterGroup {
+    leader: PerfCounter,
+    members: Vec<PerfCounter>,
+    ids: Vec<(u64, perf_format::EventAttr)>,
+}
+
+impl PerfCounterGroup {
+    fn group_ioctl(&self, request: u64) -> Result<(), io::Error> {
+        let ret = ioctl(
+            self.leader.fd,
+            request,
+            perf_event::PERF_IOC_FLAG_GROUP as ::libc::c_int,
+        );
+        if ret == -1 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// The group leader.
+    pub fn leader(&self) -> &PerfCounter {
+        &self.leader
+    }
+
+    /// The group members, in the order they were added.
+    pub fn members(&self) -> &[PerfCounter] {
+        &self.members
+    }
+
+    /// Reset all counters in the group.
+    pub fn reset(&self) -> Result<(), io::Error> {
+        self.group_ioctl(perf_event::PERF_EVENT_IOC_RESET)
+    }
+
+    /// Start measuring with all counters in the group.
+    pub fn start(&self) -> Result<(), io::Error> {
+        self.group_ioctl(perf_event::PERF_EVENT_IOC_ENABLE)
+    }
+
+    /// Stop measuring with all counters in the group.
+    pub fn stop(&self) -> Result<(), io::Error> {
+        self.group_ioctl(perf_event::PERF_EVENT_IOC_DISABLE)
+    }
+
+    /// Read all counters of the group and extrapolate the values in case the group was multiplexed.
+    ///
+    /// The values are in the same order as in `read`.
+    pub fn read_scaled(&mut self) -> Result<Vec<perf_format::ScaledValue>, io::Error> {
+        self.leader.read_fd()?.scaled_values().ok_or_else(|| {
+            Error::new(
+                io::ErrorKind::InvalidInput,
+                "Group was created without time_enabled and time_running read format",
+            )
+        })
+    }
+
+    /// Read the values of all counters in the group with a single read.
+    pub fn read(&mut self) -> Result<GroupReadFormat, io::Error> {
+        let rf = self.leader.read_fd()?;
+
+        let mut values = Vec::with_capacity(rf.values.len());
+        for v in rf.values {
+            let id = v.id.unwrap_or(0);
+            let event = self
+                .ids
+                .iter()
+                .find(|(eid, _)| *eid == id)
+                .map(|(_, attr)| *attr)
+                .ok_or_else(|| {
+                    Error::new(io::ErrorKind::InvalidData, "Unknown event id in group read")
+                })?;
+            values.push(GroupValue {
+                event,
+                id,
+                value: v.value,
+            });
+        }
+
+        Ok(GroupReadFormat {
+            time_enabled: rf.time_enabled,
+            time_running: rf.time_running,
+            values,
+        })
+    }
+}
+
+/// Reads the hardware performance counter `counter` directly from user-space.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+unsafe fn rdpmc(counter: u32) -> u64 {
+    let low: u32;
+    let high: u32;
+    core::arch::asm!("rdpmc", in("ecx") counter, out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+    ((high as u64) << 32) | (low as u64)
+}
+
+/// A counting event that is read without a system call whenever possible.
+///
+/// The first page of the event is mapped into our address space. If the kernel
+/// allows it (cap_user_rdpmc), the counter is read with the `rdpmc` instruction
+/// and combined with the offset from the control page. Otherwise, or if the
+/// event is currently not scheduled on a hardware counter, we fall back to `read(2)`.
+///
+/// Since `rdpmc` reads the counter of the current CPU, this only makes sense for
+/// counters that measure the calling thread.
+pub struct SelfMonitoringPerfCounter {
+    pc: PerfCounter,
+    map: mmap::MemoryMap,
+}
+
+impl SelfMonitoringPerfCounter {
+    /// Bit in `MMAPPage.capabilities` that signals that rdpmc can be used.
+    const CAP_USER_RDPMC: u64 = 1 << 2;
+
+    pub fn new(pc: PerfCounter) -> Result<SelfMonitoringPerfCounter, io::Error> {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let map = mmap::MemoryMap::new(
+            page_size,
+            &[
+                mmap::MapOption::MapFd(pc.fd),
+                mmap::MapOption::MapOffset(0),
+                mmap::MapOption::MapNonStandardFlags(MAP_SHARED),
+                mmap::MapOption::MapReadable,
+            ],
+        )
+        .map_err(|e| Error::other(format!("{}", e)))?;
+
+        Ok(SelfMonitoringPerfCounter { pc, map })
+    }
+
+    fn page(&self) -> *const MMAPPage {
+        self.map.data() as *const MMAPPage
+    }
+
+    /// The capabilities of the mapped control page, as reported by the kernel.
+    pub fn has_user_rdpmc(&self) -> bool {
+        let capabilities = unsafe { ptr::read_volatile(&(*self.page()).capabilities) };
+        capabilities & SelfMonitoringPerfCounter::CAP_USER_RDPMC != 0
+    }
+
+    /// Try to read the counter in user-space.
+    ///
+    /// Follows the seqlock protocol described in `linux/perf_event.h`.
+    /// Returns `None` if the counter can not be read with `rdpmc` at the moment.
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn read_user(&self) -> Option<u64> {
+        let page = self.page();
+        loop {
+            unsafe {
+                let seq = ptr::read_volatile(&(*page).lock);
+                compiler_fence(Ordering::SeqCst);
+
+                let capabilities = ptr::read_volatile(&(*page).capabilities);
+                let index = ptr::read_volatile(&(*page).index);
+                let offset = ptr::read_volatile(&(*page).offset);
+                if capabilities & SelfMonitoringPerfCounter::CAP_USER_RDPMC == 0 || index == 0 {
+                    return None;
+                }
+
+                let width = ptr::read_volatile(&(*page).pmc_width) as u32;
+                if !(1..=64).contains(&width) {
+                    return None;
+                }
+                let mut pmc = rdpmc(index - 1) as i64;
+                // The counter is only pmc_width bits wide, sign-extend to 64 bits:
+                if width < 64 {
+                    pmc <<= 64 - width;
+                    pmc >>= 64 - width;
+                }
+                let count = offset.wrapping_add(pmc);
+
+                compiler_fence(Ordering::SeqCst);
+                if ptr::read_volatile(&(*page).lock) == seq {
+                    return Some(count as u64);
+                }
+            }
+        }
+    }
+
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    fn read_user(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl AbstractPerfCounter for SelfMonitoringPerfCounter {
+    fn reset(&self) -> Result<(), io::Error> {
+        self.pc.reset()
+    }
+
+    fn start(&self) -> Result<(), io::Error> {
+        self.pc.start()
+    }
+
+    fn stop(&self) -> Result<(), io::Error> {
+        self.pc.stop()
+    }
+
+    fn read(&mut self) -> Result<u64, io::Error> {
+        match self.read_user() {
+            Some(value) => Ok(value),
+            None => self.pc.read(),
+        }
     }
 }
 
@@ -899,6 +1753,12 @@ impl MMAPRecord {
     }
 }
 
+impl<'a> From<&'a MMAPRecord> for maps::Mapping {
+    fn from(record: &'a MMAPRecord) -> maps::Mapping {
+        maps::Mapping::new(record.addr, record.len, record.pgoff, &record.filename)
+    }
+}
+
 /// This record indicates when events are lost.
 #[derive(Debug)]
 pub struct LostRecord {
@@ -1049,30 +1909,30 @@ pub struct ReadRecord {
     header: EventHeader,
     pid: u32,
     tid: u32,
-    value: FileReadFormat, // TODO with PERF_FORMAT_GROUP: values: Vec<FileReadFormat>
+    value: perf_format::ReadFormat,
 }
 
 impl ReadRecord {
-    unsafe fn copy_from_raw_ptr(ptr: *const u8) -> ReadRecord {
+    /// Returns `None` if the values don't match the `read_format` of the counter.
+    unsafe fn copy_from_raw_ptr(ptr: *const u8, flags: ReadFormatFlags) -> Option<ReadRecord> {
         let header: EventHeader = EventHeader::copy_from_raw_ptr(ptr);
         let pid: u32 = read(ptr, 8);
         let tid: u32 = read(ptr, 12);
-        let frf: FileReadFormat = FileReadFormat::copy_from_raw_ptr(ptr.offset(16));
+        let data = slice::from_raw_parts(ptr.offset(16), (header.size as usize).checked_sub(16)?);
+        let (_, value) = parser::parse_read_format(data, flags).ok()?;
 
-        ReadRecord {
+        Some(ReadRecord {
             header,
             pid,
             tid,
-            value: frf,
-        }
+            value,
+        })
     }
-}
 
-#[derive(Debug)]
-struct BranchEntry {
-    pub from: u64,
-    pub to: u64,
-    flags: u64,
+    /// The values of the counter (in the `read_format` of the counter).
+    pub fn value(&self) -> &perf_format::ReadFormat {
+        &self.value
+    }
 }
 
 /// This record indicates a sample.
@@ -1100,10 +1960,8 @@ pub struct SampleRecord {
     /// if PERF_SAMPLE_PERIOD
     period: u64,
 
-    /// if PERF_SAMPLE_READ
-    /// # TODO
-    /// FILE GROUP FORMAT is different...
-    v: FileReadFormat,
+    /// if PERF_SAMPLE_READ (in the `read_format` of the counter)
+    v: Option<perf_format::ReadFormat>,
 
     //u64   nr;         /* if PERF_SAMPLE_CALLCHAIN */
     //u64   ips[nr];    /* if PERF_SAMPLE_CALLCHAIN */
@@ -1124,6 +1982,9 @@ pub struct SampleRecord {
     /// if PERF_SAMPLE_REGS_USER
     regs: Vec<u64>,
 
+    /// `sample_regs_user` of the counter, the registers in `regs`.
+    regs_mask: u64,
+
     /// u64   size;       /* if PERF_SAMPLE_STACK_USER */
     /// char  data[size]; /* if PERF_SAMPLE_STACK_USER */
     user_stack: Vec<u8>,
@@ -1137,6 +1998,35 @@ pub struct SampleRecord {
 }
 
 impl SampleRecord {
+    /// The sampled branch stack (if PERF_SAMPLE_BRANCH_STACK), most recent branch first.
+    pub fn lbr(&self) -> &[BranchEntry] {
+        &self.lbr
+    }
+
+    /// The sampled user mode registers by name (if PERF_SAMPLE_REGS_USER).
+    ///
+    /// Returns `None` if the sample has no user mode registers (e.g., it was taken in
+    /// a kernel thread).
+    pub fn user_regs(&self) -> Option<BTreeMap<&'static str, u64>> {
+        Some(self.user_arch()?.decode(self.regs_mask, &self.regs))
+    }
+
+    /// The register set of the sampled task, it depends on the ABI of the task.
+    pub fn user_arch(&self) -> Option<regs::RegArch> {
+        regs::RegArch::native()?.for_abi(self.abi)
+    }
+
+    /// The copy of the user stack (if PERF_SAMPLE_STACK_USER), starting at the
+    /// sampled stack pointer.
+    pub fn user_stack(&self) -> &[u8] {
+        &self.user_stack
+    }
+
+    /// The values of the counter when the sample was taken (if PERF_SAMPLE_READ).
+    pub fn read_format(&self) -> Option<&perf_format::ReadFormat> {
+        self.v.as_ref()
+    }
+
     unsafe fn copy_from_raw_ptr(ptr: *const u8) -> SampleRecord {
         let header: EventHeader = EventHeader::copy_from_raw_ptr(ptr);
         let ip: u64 = read(ptr, 8);
@@ -1150,13 +2040,13 @@ impl SampleRecord {
         let res: u32 = read(ptr, 56);
         let period: u64 = read(ptr, 64);
 
-        // TODO:
-        let v: FileReadFormat = FileReadFormat::copy_from_raw_ptr(ptr.offset(72));
+        let v: Option<perf_format::ReadFormat> = None;
         let ips: Vec<u64> = Vec::new();
         let raw_sample: Vec<u8> = Vec::new();
         let lbr: Vec<BranchEntry> = Vec::new();
         let abi: u64 = 0;
         let regs: Vec<u64> = Vec::new();
+        let regs_mask: u64 = 0;
         let user_stack: Vec<u8> = Vec::new();
         let dyn_size: u64 = 0;
         let weight: u64 = 0;
@@ -1180,6 +2070,7 @@ impl SampleRecord {
             lbr,
             abi,
             regs,
+            regs_mask,
             user_stack,
             dyn_size,
             weight,
@@ -1250,12 +2141,37 @@ impl Iterator for SamplingPerfCounter {
                     Some(Event::Fork(record))
                 }
                 perf_event::PERF_RECORD_READ => {
-                    let record: ReadRecord = unsafe { ReadRecord::copy_from_raw_ptr(event_ptr) };
-                    Some(Event::Read(record))
+                    let flags = self.pc.attributes.read_format;
+                    let record: Option<ReadRecord> =
+                        unsafe { ReadRecord::copy_from_raw_ptr(event_ptr, flags) };
+                    record.map(Event::Read)
                 }
                 perf_event::PERF_RECORD_SAMPLE => {
-                    let record: SampleRecord =
+                    let mut record: SampleRecord =
                         unsafe { SampleRecord::copy_from_raw_ptr(event_ptr) };
+                    let attributes = &self.pc.attributes;
+                    if attributes.sample_type.has_branch_stack()
+                        || attributes.sample_type.has_regs_user()
+                        || attributes.sample_type.has_stack_user()
+                        || attributes.sample_type.has_read()
+                    {
+                        let body = unsafe {
+                            slice::from_raw_parts(
+                                event_ptr.add(mem::size_of::<EventHeader>()),
+                                (event.size as usize).saturating_sub(mem::size_of::<EventHeader>()),
+                            )
+                        };
+                        if let Ok((_, sample)) = parser::parse_sample_record(body, attributes) {
+                            record.v = sample.v;
+                            record.lbr = sample.lbr.unwrap_or_default();
+                            record.abi = sample.abi_user.unwrap_or(0);
+                            record.regs = sample.regs_user.unwrap_or_default();
+                            record.regs_mask = attributes.sample_regs_user;
+                            record.user_stack = sample.user_stack.unwrap_or_default();
+                            record.dyn_size = sample.dyn_size.unwrap_or(0);
+                            record.user_stack.truncate(record.dyn_size as usize);
+                        }
+                    }
                     Some(Event::Sample(record))
                 }
                 perf_event::PERF_RECORD_MMAP2 => {
@@ -58,25 +58,26 @@ fn no_event(input: &[u8]) -> IResult<&[u8], EventData> {
     Ok((input, EventData::None))
 }
 
-// TODO: Needs sample flags!
-named!(pub parse_sample_id<&[u8], SampleId>,
+/// Parse the `sample_id` of a non-sample record, `flags` is the `sample_type` of the event.
+pub fn parse_sample_id(input: &[u8], flags: SampleFormatFlags) -> IResult<&[u8], SampleId> {
     do_parse!(
-        ptid: parse_thread_id >>
-        time: le_u64 >>
-        id: le_u64 >>
-        stream_id: le_u64 >>
-        cpu: parse_cpu >>
-        identifier: le_u64 >>
-        (SampleId {
-            ptid: ptid,
-            time: time,
-            id: id,
-            stream_id: stream_id,
-            cpu: cpu,
-            identifier: identifier
-        })
+        input,
+        ptid: cond!(flags.has_tid(), parse_thread_id)
+            >> time: cond!(flags.has_time(), le_u64)
+            >> id: cond!(flags.has_sample_id(), le_u64)
+            >> stream_id: cond!(flags.has_stream_id(), le_u64)
+            >> cpu: cond!(flags.has_cpu(), parse_cpu)
+            >> identifier: cond!(flags.has_identifier(), le_u64)
+            >> (SampleId {
+                ptid,
+                time,
+                id,
+                stream_id,
+                cpu,
+                identifier,
+            })
     )
-);
+}
 
 named!(pub parse_thread_id<&[u8], ThreadId>,
     do_parse!(
@@ -195,7 +196,6 @@ named!(pub parse_mmap2_record<&[u8], MMAP2Record>,
         prot: le_u32 >>
         flags: le_u32 >>
         filename: parse_c_string >>
-        // TODO: sample_id: parse_sample_id,
         (MMAP2Record {
             ptid: ptid,
             addr: addr,
@@ -212,13 +212,17 @@ named!(pub parse_mmap2_record<&[u8], MMAP2Record>,
     )
 );
 
-pub fn parse_read_value(
-    input: &[u8],
-    flags: ReadFormatFlags,
-) -> IResult<&[u8], (u64, Option<u64>)> {
+pub fn parse_read_value(input: &[u8], flags: ReadFormatFlags) -> IResult<&[u8], ReadValue> {
     do_parse!(
         input,
-        value: le_u64 >> id: cond!(flags.has_id(), le_u64) >> (value, id)
+        value: le_u64
+            >> id: cond!(flags.has_id(), le_u64)
+            >> lost: cond!(flags.has_lost(), le_u64)
+            >> (ReadValue {
+                value: value,
+                id: id,
+                lost: lost
+            })
     )
 }
 
@@ -243,15 +247,37 @@ pub fn parse_read_format(input: &[u8], flags: ReadFormatFlags) -> IResult<&[u8],
                 >> time_enabled: cond!(flags.has_total_time_enabled(), le_u64)
                 >> time_running: cond!(flags.has_total_time_running(), le_u64)
                 >> id: cond!(flags.has_id(), le_u64)
+                >> lost: cond!(flags.has_lost(), le_u64)
                 >> (ReadFormat {
                     time_enabled: time_enabled,
                     time_running: time_running,
-                    values: vec![(value, id)]
+                    values: vec![ReadValue {
+                        value: value,
+                        id: id,
+                        lost: lost
+                    }]
                 })
         )
     }
 }
 
+pub fn parse_read_record<'a>(
+    input: &'a [u8],
+    attr: &'a EventAttr,
+) -> IResult<&'a [u8], ReadRecord> {
+    do_parse!(
+        input,
+        pid: le_u32
+            >> tid: le_u32
+            >> value: call!(parse_read_format, attr.read_format)
+            >> (ReadRecord {
+                pid: pid,
+                tid: tid,
+                value: value
+            })
+    )
+}
+
 named!(pub parse_branch_entry<&[u8], BranchEntry>,
     do_parse!(
         from: le_u64 >>
@@ -265,18 +291,26 @@ named!(pub parse_branch_entry<&[u8], BranchEntry>,
     )
 );
 
-pub fn parse_branch_entries(
-    input: &[u8],
-    flags: SampleFormatFlags,
-) -> IResult<&[u8], Vec<BranchEntry>> {
-    // TODO: bug? https://github.com/Geal/nom/issues/302
-    assert!(flags.has_branch_stack() && flags.has_regs_user());
+/// Parses the branch stack of a sample.
+///
+/// The layout depends on `attr.branch_sample_type`: with `PERF_SAMPLE_BRANCH_HW_INDEX`
+/// the entries are preceded by the hardware index, with `PERF_SAMPLE_BRANCH_COUNTERS`
+/// they are followed by a counter value per entry (both are skipped).
+pub fn parse_branch_entries<'a>(
+    input: &'a [u8],
+    attr: &'a EventAttr,
+) -> IResult<&'a [u8], Vec<BranchEntry>> {
+    let branch_type = attr.branch_sample_type;
     do_parse!(
         input,
-        // TODO: bug? https://github.com/Geal/nom/issues/302
-        //bnr: cond!(flags.has_branch_stack(), le_u64) ~
-        //entries: cond!(flags.has_branch_stack() && flags.has_regs_user(), count!(parse_branch_entry, 3)),
-        bnr: le_u64 >> entries: count!(parse_branch_entry, bnr as usize) >> (entries)
+        bnr: le_u64
+            >> cond!(branch_type.has_hw_index(), le_u64)
+            >> entries: count!(parse_branch_entry, bnr as usize)
+            >> cond!(
+                branch_type.has_counters(),
+                call!(parse_vec_u64_variable, bnr as usize)
+            )
+            >> (entries)
     )
 }
 
@@ -301,11 +335,11 @@ pub fn parse_sample_record<'a>(
             >> v: cond!(flags.has_read(), call!(parse_read_format, attr.read_format))
             >> ips: cond!(flags.has_callchain(), parse_vec_u64)
             >> raw: cond!(flags.has_raw(), parse_vec_u32_u8)
-            >> lbr: cond!(flags.has_branch_stack(), call!(parse_branch_entries, flags))
-            >> abi_user: cond!(flags.has_stack_user(), le_u64)
+            >> lbr: cond!(flags.has_branch_stack(), call!(parse_branch_entries, attr))
+            >> abi_user: cond!(flags.has_regs_user(), le_u64)
             >> regs_user:
                 cond!(
-                    flags.has_stack_user(),
+                    abi_user.unwrap_or(0) != 0,
                     call!(parse_vec_u64_variable, regcnt_user)
                 )
             >> user_stack_len: cond!(flags.has_stack_user(), le_u64)
@@ -325,7 +359,7 @@ pub fn parse_sample_record<'a>(
             >> abi: cond!(flags.has_regs_intr(), le_u64)
             >> regs_intr:
                 cond!(
-                    flags.has_regs_intr(),
+                    abi.unwrap_or(0) != 0,
                     call!(parse_vec_u64_variable, regcnt_intr)
                 )
             >> (SampleRecord {
@@ -360,7 +394,6 @@ pub fn parse_comm_record(input: &[u8]) -> IResult<&[u8], CommRecord> {
         input,
         ptid: parse_thread_id >>
         comm: parse_c_string >>
-        // TODO: sample_id: parse_sample_id,
         (CommRecord {
             ptid: ptid,
             comm: unsafe { String::from_utf8_unchecked(comm.to_vec()) }
@@ -369,7 +402,46 @@ pub fn parse_comm_record(input: &[u8]) -> IResult<&[u8], CommRecord> {
 }
 
 /// Parse an event record.
+///
+/// If the (first) event has `sample_id_all` set, the `sample_id` at the end of
+/// records the kernel generates is parsed as well.
 pub fn parse_event<'a>(input: &'a [u8], attrs: &'a Vec<EventAttr>) -> IResult<&'a [u8], Event> {
+    let (rest, mut event) = parse_event_record(input, attrs)?;
+    if let Some(attr) = attrs.first() {
+        event.sample_id = parse_trailing_sample_id(input, &event.header, attr);
+    }
+    Ok((rest, event))
+}
+
+/// Parses the `sample_id` at the end of the record in `input`.
+fn parse_trailing_sample_id(
+    input: &[u8],
+    header: &EventHeader,
+    attr: &EventAttr,
+) -> Option<SampleId> {
+    let kernel_record = match header.event_type {
+        EventType::Sample | EventType::BuildId | EventType::FinishedRound => false,
+        EventType::Unknown(t) => t < 64,
+        _ => true,
+    };
+    if !kernel_record
+        || !attr
+            .settings
+            .contains(EventAttrFlags::EVENT_ATTR_SAMPLE_ID_ALL)
+    {
+        return None;
+    }
+
+    let size = attr.sample_type.sample_id_size();
+    let record = input.get(..header.size())?;
+    // The header itself is 8 bytes:
+    let start = record.len().checked_sub(size).filter(|start| *start >= 8)?;
+    parse_sample_id(&record[start..], attr.sample_type)
+        .ok()
+        .map(|(_, id)| id)
+}
+
+fn parse_event_record<'a>(input: &'a [u8], attrs: &'a Vec<EventAttr>) -> IResult<&'a [u8], Event> {
     do_parse!(
         input,
         header: parse_event_header
@@ -390,6 +462,9 @@ pub fn parse_event<'a>(input: &'a [u8], attrs: &'a Vec<EventAttr>) -> IResult<&'
                     ) | cond_reduce!(
                         header.event_type == EventType::Sample,
                         map!(call!(parse_sample_record, &attrs[0]), EventData::Sample)
+                    ) | cond_reduce!(
+                        header.event_type == EventType::Read,
+                        map!(call!(parse_read_record, &attrs[0]), EventData::Read)
                     ) | cond_reduce!(
                         header.event_type == EventType::Fork,
                         map!(parse_fork_record, EventData::Fork)
@@ -402,7 +477,7 @@ pub fn parse_event<'a>(input: &'a [u8], attrs: &'a Vec<EventAttr>) -> IResult<&'
                     ) | cond_reduce!(
                         header.event_type == EventType::BuildId,
                         map!(
-                            call!(parse_build_id_record, header.size()),
+                            call!(parse_build_id_record, &header),
                             EventData::BuildId
                         )
                     ) | cond_reduce!(header.event_type == EventType::FinishedRound, no_event)
@@ -410,7 +485,8 @@ pub fn parse_event<'a>(input: &'a [u8], attrs: &'a Vec<EventAttr>) -> IResult<&'
                 )
             >> (Event {
                 header: header,
-                data: event
+                data: event,
+                sample_id: None
             })
     )
 }
@@ -539,17 +615,27 @@ named!(pub parse_group_descriptions<&[u8], Vec<GroupDesc> >,
 
 pub fn parse_build_id_record<'a>(
     input: &'a [u8],
-    record_size: usize,
+    header: &EventHeader,
 ) -> IResult<&'a [u8], BuildIdRecord> {
     do_parse!(
         input,
         pid: le_i32 >>
         build_id: take!(24) >>
-        filename: take!(record_size - 4 - 24) >> // header.size - offsetof(struct build_id_event, filename)
+        // header.size - offsetof(struct perf_record_header_build_id, filename)
+        filename: take!(header.size().saturating_sub(8 + 4 + 24)) >>
         (BuildIdRecord {
             pid: pid,
-            build_id: build_id.to_owned(),
-            filename: unsafe { String::from_utf8_unchecked(filename.to_vec()) }
+            build_id: {
+                let size = if header.misc & PERF_RECORD_MISC_BUILD_ID_SIZE != 0 {
+                    (build_id[20] as usize).min(20)
+                } else {
+                    20
+                };
+                build_id[..size].to_owned()
+            },
+            filename: String::from_utf8_lossy(filename)
+                .trim_end_matches('\0')
+                .to_string()
         })
     )
 }
@@ -612,8 +698,37 @@ named!(pub parse_header<&[u8], PerfFileHeader>,
     )
 );
 
-// Parse a perf header
-named!(pub parse_event_attr<&[u8], EventAttr>,
+/// Parses a `perf_event_attr` of any size.
+///
+/// Writers store the version they were built with: fields an older writer does not know
+/// are left zero, fields that are newer than `EventAttr` are skipped.
+pub fn parse_event_attr(input: &[u8]) -> IResult<&[u8], EventAttr> {
+    if input.len() < PERF_ATTR_SIZE_VER0 as usize {
+        return Err(Err::Incomplete(Needed::Size(PERF_ATTR_SIZE_VER0 as usize)));
+    }
+
+    // The size field is what the writer used, an unset size means the first version.
+    // The kernel rejects anything smaller than the first version:
+    let size = match le_u32(&input[4..])?.1 {
+        0 => PERF_ATTR_SIZE_VER0 as usize,
+        size if size < PERF_ATTR_SIZE_VER0 => {
+            return Err(Err::Error(error_position!(input, ErrorKind::Custom(1))))
+        }
+        size => std::cmp::min(size as usize, input.len()),
+    };
+
+    // Only the writer's bytes, the fields it does not know stay zero:
+    let mut buf = [0u8; PERF_ATTR_SIZE_VER8 as usize];
+    let len = std::cmp::min(size, buf.len());
+    buf[..len].copy_from_slice(&input[..len]);
+    let (_, attr) = match parse_event_attr_ver8(&buf) {
+        Ok(r) => r,
+        Err(_) => return Err(Err::Error(error_position!(input, ErrorKind::Custom(0)))),
+    };
+    Ok((&input[size..], attr))
+}
+
+named!(parse_event_attr_ver8<&[u8], EventAttr>,
     do_parse!(
         attr_type: le_u32 >>
         size: le_u32 >>
@@ -632,7 +747,12 @@ named!(pub parse_event_attr<&[u8], EventAttr>,
         clock_id: le_i32 >>
         sample_regs_intr: le_u64 >>
         aux_watermark: le_u32 >>
-        le_u32 >> // reserved
+        sample_max_stack: le_u16 >>
+        reserved_2: le_u16 >>
+        aux_sample_size: le_u32 >>
+        aux_action: le_u32 >>
+        sig_data: le_u64 >>
+        config3: le_u64 >>
         (EventAttr {
             attr_type: attr_type,
             size: size,
@@ -645,12 +765,17 @@ named!(pub parse_event_attr<&[u8], EventAttr>,
             bp_type: bp_type,
             config1_or_bp_addr: config1_or_bp_addr,
             config2_or_bp_len: config2_or_bp_len,
-            branch_sample_type: branch_sample_type,
+            branch_sample_type: BranchSampleType::from_bits_truncate(branch_sample_type),
             sample_regs_user: sample_regs_user,
             sample_stack_user: sample_stack_user,
             clock_id: clock_id,
             sample_regs_intr: sample_regs_intr,
             aux_watermark: aux_watermark,
-            reserved: 0
+            sample_max_stack: sample_max_stack,
+            reserved_2: reserved_2,
+            aux_sample_size: aux_sample_size,
+            aux_action: aux_action,
+            sig_data: sig_data,
+            config3: config3,
         })
 ));
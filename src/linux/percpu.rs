@@ -0,0 +1,191 @@
+//! Open the same event on several CPUs and treat the counters as one.
+//!
+//! A counter opened with `on_cpu` only sees what runs on that particular CPU.
+//! `PerCpuCounter` opens one counter per CPU (by default every online CPU),
+//! controls them together and sums up their values. It counts everything that runs
+//! on these CPUs (like `perf stat -a`), unless it is opened with `for_process`.
+//!
+//! # Example
+//! ```no_run
+//! use perfcnt::AbstractPerfCounter;
+//! use perfcnt::linux::{HardwareEventType, PerfCounterBuilderLinux};
+//! use perfcnt::linux::percpu::PerCpuCounter;
+//!
+//! let builder = PerfCounterBuilderLinux::from_hardware_event(HardwareEventType::Instructions);
+//! let mut pc = PerCpuCounter::new(&builder).expect("Could not create counters");
+//! pc.start().expect("Can not start the counters");
+//! // ...
+//! pc.stop().expect("Can not stop the counters");
+//! let values = pc.read_per_cpu().expect("Can not read the counters");
+//! for (cpu, value) in values.per_cpu.iter() {
+//!     println!("CPU {}: {}", cpu, value);
+//! }
+//! println!("Total: {}", values.total);
+//! ```
+
+use std::fs;
+use std::io;
+use std::io::Error;
+use std::path::Path;
+
+use super::perf_format::ScaledValue;
+use super::{PerfCounter, PerfCounterBuilderLinux};
+use crate::AbstractPerfCounter;
+
+/// Location of the online CPU list relative to the sysfs root.
+const ONLINE_CPUS: &str = "devices/system/cpu/online";
+
+/// Parses a kernel CPU list (e.g., `0-3,5,7-8`) into the individual CPU numbers.
+pub fn parse_cpu_list(list: &str) -> Result<Vec<usize>, io::Error> {
+    let invalid = |part: &str| {
+        Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid entry '{}' in CPU list", part),
+        )
+    };
+
+    let mut cpus = Vec::new();
+    for part in list.trim().split(',').filter(|p| !p.is_empty()) {
+        let mut range = part.splitn(2, '-');
+        let start: usize = range
+            .next()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| invalid(part))?;
+        let end: usize = match range.next() {
+            Some(s) => s.parse().map_err(|_| invalid(part))?,
+            None => start,
+        };
+        if end < start {
+            return Err(invalid(part));
+        }
+        cpus.extend(start..=end);
+    }
+
+    Ok(cpus)
+}
+
+/// The CPUs that are currently online, according to `/sys`.
+pub fn online_cpus() -> Result<Vec<usize>, io::Error> {
+    online_cpus_in(Path::new("/sys"))
+}
+
+/// The CPUs that are currently online, according to a sysfs tree mounted at `sysfs_root`.
+pub fn online_cpus_in(sysfs_root: &Path) -> Result<Vec<usize>, io::Error> {
+    let list = fs::read_to_string(sysfs_root.join(ONLINE_CPUS))?;
+    parse_cpu_list(&list)
+}
+
+/// Values of a `PerCpuCounter`, broken down by CPU.
+#[derive(Debug, Clone)]
+pub struct PerCpuValues {
+    /// (cpu, value) for every CPU, in the order the counters were opened.
+    pub per_cpu: Vec<(usize, u64)>,
+    /// Sum of all values.
+    pub total: u64,
+}
+
+/// The same event, counted on several CPUs.
+pub struct PerCpuCounter {
+    counters: Vec<(usize, PerfCounter)>,
+}
+
+impl PerCpuCounter {
+    /// Open `builder` on every online CPU, counting everything that happens on the system.
+    pub fn new(builder: &PerfCounterBuilderLinux) -> Result<PerCpuCounter, io::Error> {
+        PerCpuCounter::with_sysfs_root(builder, Path::new("/sys"))
+    }
+
+    /// Open `builder` on every CPU that is online according to the sysfs tree at `sysfs_root`.
+    pub fn with_sysfs_root(
+        builder: &PerfCounterBuilderLinux,
+        sysfs_root: &Path,
+    ) -> Result<PerCpuCounter, io::Error> {
+        let cpus = online_cpus_in(sysfs_root)?;
+        PerCpuCounter::on_cpus(builder, &cpus)
+    }
+
+    /// Open `builder` on each CPU in `cpus`, counting everything that runs on them.
+    ///
+    /// All counters start out disabled, use `start` to enable them.
+    pub fn on_cpus(
+        builder: &PerfCounterBuilderLinux,
+        cpus: &[usize],
+    ) -> Result<PerCpuCounter, io::Error> {
+        let mut builder = builder.clone();
+        builder.for_all_pids();
+        PerCpuCounter::for_process(&builder, cpus)
+    }
+
+    /// Open `builder` on each CPU in `cpus` for the process (or cgroup) the builder is
+    /// configured for, i.e., only count what that process does on each of these CPUs.
+    ///
+    /// All counters start out disabled, use `start` to enable them.
+    pub fn for_process(
+        builder: &PerfCounterBuilderLinux,
+        cpus: &[usize],
+    ) -> Result<PerCpuCounter, io::Error> {
+        let mut counters = Vec::with_capacity(cpus.len());
+        for &cpu in cpus {
+            let mut b = builder.clone();
+            b.disable().on_cpu(cpu as isize);
+            counters.push((cpu, b.finish()?));
+        }
+
+        Ok(PerCpuCounter { counters })
+    }
+
+    /// The CPUs we have a counter on.
+    pub fn cpus(&self) -> Vec<usize> {
+        self.counters.iter().map(|(cpu, _)| *cpu).collect()
+    }
+
+    /// Read the counters of all CPUs.
+    pub fn read_per_cpu(&mut self) -> Result<PerCpuValues, io::Error> {
+        let mut per_cpu = Vec::with_capacity(self.counters.len());
+        let mut total: u64 = 0;
+        for (cpu, pc) in self.counters.iter_mut() {
+            let value = pc.read()?;
+            total = total.wrapping_add(value);
+            per_cpu.push((*cpu, value));
+        }
+
+        Ok(PerCpuValues { per_cpu, total })
+    }
+
+    /// Read the counters of all CPUs and extrapolate the values in case they were multiplexed.
+    pub fn read_scaled(&mut self) -> Result<Vec<(usize, ScaledValue)>, io::Error> {
+        let mut values = Vec::with_capacity(self.counters.len());
+        for (cpu, pc) in self.counters.iter_mut() {
+            values.push((*cpu, pc.read_scaled()?));
+        }
+        Ok(values)
+    }
+}
+
+impl AbstractPerfCounter for PerCpuCounter {
+    fn reset(&self) -> Result<(), io::Error> {
+        for (_, pc) in self.counters.iter() {
+            pc.reset()?;
+        }
+        Ok(())
+    }
+
+    fn start(&self) -> Result<(), io::Error> {
+        for (_, pc) in self.counters.iter() {
+            pc.start()?;
+        }
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), io::Error> {
+        for (_, pc) in self.counters.iter() {
+            pc.stop()?;
+        }
+        Ok(())
+    }
+
+    /// The sum over all CPUs.
+    fn read(&mut self) -> Result<u64, io::Error> {
+        Ok(self.read_per_cpu()?.total)
+    }
+}
@@ -143,7 +143,17 @@ pub const PERF_SAMPLE_BRANCH_IND_CALL: ::libc::c_uint = 64;
 pub const PERF_SAMPLE_BRANCH_ABORT_TX: ::libc::c_uint = 128;
 pub const PERF_SAMPLE_BRANCH_IN_TX: ::libc::c_uint = 256;
 pub const PERF_SAMPLE_BRANCH_NO_TX: ::libc::c_uint = 512;
-pub const PERF_SAMPLE_BRANCH_MAX: ::libc::c_uint = 1024;
+pub const PERF_SAMPLE_BRANCH_COND: ::libc::c_uint = 1024;
+pub const PERF_SAMPLE_BRANCH_CALL_STACK: ::libc::c_uint = 2048;
+pub const PERF_SAMPLE_BRANCH_IND_JUMP: ::libc::c_uint = 4096;
+pub const PERF_SAMPLE_BRANCH_CALL: ::libc::c_uint = 8192;
+pub const PERF_SAMPLE_BRANCH_NO_FLAGS: ::libc::c_uint = 16384;
+pub const PERF_SAMPLE_BRANCH_NO_CYCLES: ::libc::c_uint = 32768;
+pub const PERF_SAMPLE_BRANCH_TYPE_SAVE: ::libc::c_uint = 65536;
+pub const PERF_SAMPLE_BRANCH_HW_INDEX: ::libc::c_uint = 131072;
+pub const PERF_SAMPLE_BRANCH_PRIV_SAVE: ::libc::c_uint = 262144;
+pub const PERF_SAMPLE_BRANCH_COUNTERS: ::libc::c_uint = 524288;
+pub const PERF_SAMPLE_BRANCH_MAX: ::libc::c_uint = 1048576;
 pub type Enum_perf_sample_regs_abi = ::libc::c_uint;
 pub const PERF_SAMPLE_REGS_ABI_NONE: ::libc::c_uint = 0;
 pub const PERF_SAMPLE_REGS_ABI_32: ::libc::c_uint = 1;
@@ -10,6 +10,7 @@
 
 use super::parser::*;
 use super::perf_format::*;
+use super::regs::RegArch;
 use nom::*;
 
 macro_rules! stderr {
@@ -103,16 +104,28 @@ impl PerfFile {
         }
     }
 
+    /// The first build id record, see `get_build_ids`.
     pub fn get_build_id(&self) -> Option<BuildIdRecord> {
-        self.get_section_slice(HeaderFlag::BuildId)
-            .and_then(|slice| {
-                iresult_to_option(do_parse!(
-                    slice,
-                    header: parse_event_header
-                        >> build_id: call!(parse_build_id_record, header.size())
-                        >> (build_id)
-                ))
-            })
+        self.get_build_ids().into_iter().next()
+    }
+
+    /// The build ids of the files that were hit by samples (`perf record` adds
+    /// them when it finishes), use a `BuildIdResolver` to find the matching binaries.
+    pub fn get_build_ids(&self) -> Vec<BuildIdRecord> {
+        let mut records = Vec::new();
+        let mut slice = self.get_section_slice(HeaderFlag::BuildId).unwrap_or(&[]);
+        while let Ok((_, header)) = parse_event_header(slice) {
+            if header.size() < 8 || header.size() > slice.len() {
+                break;
+            }
+            let (record, rest) = slice.split_at(header.size());
+            if let Some(build_id) = iresult_to_option(parse_build_id_record(&record[8..], &header))
+            {
+                records.push(build_id);
+            }
+            slice = rest;
+        }
+        records
     }
 
     pub fn get_hostname(&self) -> Option<String> {
@@ -135,6 +148,13 @@ impl PerfFile {
             .and_then(|slice| iresult_to_option(parse_perf_string(slice)))
     }
 
+    /// The register set of the machine the file was recorded on, pass it to
+    /// `SampleRecord::user_regs` to name the sampled registers.
+    pub fn get_reg_arch(&self) -> Option<RegArch> {
+        self.get_arch()
+            .and_then(|arch| RegArch::from_machine(&arch))
+    }
+
     pub fn get_nr_cpus(&self) -> Option<NrCpus> {
         self.get_section_slice(HeaderFlag::NrCpus)
             .and_then(|slice| iresult_to_option(parse_nrcpus(slice)))
@@ -3,8 +3,13 @@
 //! In order to parse these structures from perf files or perf MMAP buffers, please
 //! have a look at the functions in parser.rs.
 
+use std::collections::BTreeMap;
+
 use bitflags::*;
 
+use super::perf_event;
+use super::regs::RegArch;
+
 /// Unique thread descriptor. Used in many different perf structures.
 #[derive(Debug)]
 pub struct ThreadId {
@@ -19,26 +24,42 @@ pub struct Cpu {
     pub res: u32,
 }
 
+/// Identifies the task and time of a non-sample record, appended to the record
+/// if the event has `sample_id_all` set.
 #[derive(Debug)]
 pub struct SampleId {
     /// if PERF_SAMPLE_TID set
-    pub ptid: ThreadId,
+    pub ptid: Option<ThreadId>,
     /// if PERF_SAMPLE_TIME set
-    pub time: u64,
+    pub time: Option<u64>,
     /// if PERF_SAMPLE_ID set
-    pub id: u64,
+    pub id: Option<u64>,
     /// if PERF_SAMPLE_STREAM_ID set
-    pub stream_id: u64,
+    pub stream_id: Option<u64>,
     /// if PERF_SAMPLE_CPU set
-    pub cpu: Cpu,
+    pub cpu: Option<Cpu>,
     /// if PERF_SAMPLE_IDENTIFIER set
-    pub identifier: u64,
+    pub identifier: Option<u64>,
 }
 
 #[derive(Debug)]
 pub struct Event {
     pub header: EventHeader,
     pub data: EventData,
+    /// if `sample_id_all` is set (not for samples, their fields are in the `SampleRecord`)
+    pub sample_id: Option<SampleId>,
+}
+
+impl Event {
+    /// When the event happened, if the record or its `sample_id` has a timestamp.
+    pub fn time(&self) -> Option<u64> {
+        match self.data {
+            EventData::Sample(ref sample) => sample.time,
+            EventData::Fork(ref fork) => Some(fork.time),
+            EventData::Exit(ref exit) => Some(exit.time),
+            _ => self.sample_id.as_ref().and_then(|id| id.time),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -50,7 +71,7 @@ pub enum EventData {
     Throttle(ThrottleRecord),
     Unthrottle(UnthrottleRecord),
     Fork(ForkRecord),
-    //Read(ReadRecord),
+    Read(ReadRecord),
     Sample(SampleRecord),
     MMAP2(MMAP2Record),
     BuildId(BuildIdRecord),
@@ -64,10 +85,22 @@ pub struct EventHeader {
     pub size: u16,
 }
 
+/// Bit in `EventHeader.misc` of a COMM record that is caused by an exec.
+pub const PERF_RECORD_MISC_COMM_EXEC: u16 = 1 << 13;
+
+/// Bit in `EventHeader.misc` of a BUILD_ID record that has the size of the build id
+/// in the byte after it (otherwise it is 20 bytes, a SHA-1).
+pub const PERF_RECORD_MISC_BUILD_ID_SIZE: u16 = 1 << 15;
+
 impl EventHeader {
     pub fn size(&self) -> usize {
         self.size as usize
     }
+
+    /// Is this a COMM record of an exec (needs `comm_exec`)?
+    pub fn is_comm_exec(&self) -> bool {
+        self.event_type == EventType::Comm && self.misc & PERF_RECORD_MISC_COMM_EXEC != 0
+    }
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -134,7 +167,6 @@ pub struct ForkRecord {
     pub tid: u32,
     pub ptid: u32,
     pub time: u64,
-    // TOOD: sample_id
 }
 
 /// This record indicates a process exit event.
@@ -144,7 +176,7 @@ pub struct ExitRecord {
     pub ppid: u32,
     pub tid: u32,
     pub ptid: u32,
-    pub time: u64, // TOOD: sample_id
+    pub time: u64,
 }
 
 #[derive(Debug)]
@@ -185,7 +217,17 @@ pub struct MMAP2Record {
     pub prot: u32,
     pub flags: u32,
     pub filename: String,
-    //TODO: sample_id: SampleId
+}
+
+/// A single counter value as it appears in a read format.
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ReadValue {
+    /// The value of the event
+    pub value: u64,
+    /// if PERF_FORMAT_ID
+    pub id: Option<u64>,
+    /// if PERF_FORMAT_LOST
+    pub lost: Option<u64>,
 }
 
 /// We use the same read format for READ_FORMAT_GROUP and non-grouped reads for simplicity
@@ -195,8 +237,95 @@ pub struct ReadFormat {
     pub time_enabled: Option<u64>,
     /// if PERF_FORMAT_TOTAL_TIME_RUNNING
     pub time_running: Option<u64>,
-    /// Collection of (value, Some(id) if PERF_FORMAT_ID)
-    pub values: Vec<(u64, Option<u64>)>,
+    /// One value per event (more than one only if PERF_FORMAT_GROUP)
+    pub values: Vec<ReadValue>,
+}
+
+impl ReadFormat {
+    /// The value of the first (or only) event.
+    pub fn value(&self) -> Option<u64> {
+        self.values.first().map(|v| v.value)
+    }
+
+    /// Extrapolate all values to the time the events were enabled.
+    ///
+    /// Returns `None` if the read format did not include both
+    /// PERF_FORMAT_TOTAL_TIME_ENABLED and PERF_FORMAT_TOTAL_TIME_RUNNING.
+    pub fn scaled_values(&self) -> Option<Vec<ScaledValue>> {
+        match (self.time_enabled, self.time_running) {
+            (Some(enabled), Some(running)) => Some(
+                self.values
+                    .iter()
+                    .map(|v| ScaledValue::new(v.value, enabled, running))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// A counter value corrected for multiplexing.
+///
+/// If more events are active than the PMU has counters, the kernel rotates
+/// them and every event only counts for a fraction of the time it was enabled.
+/// The scaled value assumes the event occurred at the same rate while it was
+/// not scheduled.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ScaledValue {
+    /// The extrapolated value, `None` if the event was never enabled or never scheduled
+    /// on the PMU.
+    pub scaled: Option<u64>,
+    /// The value as read from the counter.
+    pub raw: u64,
+    /// Time the event was enabled.
+    pub time_enabled: u64,
+    /// Time the event was actually counting.
+    pub time_running: u64,
+}
+
+impl ScaledValue {
+    pub fn new(raw: u64, time_enabled: u64, time_running: u64) -> ScaledValue {
+        let scaled = if time_running == 0 {
+            None
+        } else if time_running == time_enabled {
+            Some(raw)
+        } else {
+            Some((raw as u128 * time_enabled as u128 / time_running as u128) as u64)
+        };
+
+        ScaledValue {
+            scaled,
+            raw,
+            time_enabled,
+            time_running,
+        }
+    }
+
+    /// Fraction of the enabled time the event was counting (between 0.0 and 1.0).
+    pub fn running_ratio(&self) -> f64 {
+        if self.time_enabled == 0 {
+            0.0
+        } else {
+            self.time_running as f64 / self.time_enabled as f64
+        }
+    }
+
+    /// The event had to share the PMU with other events.
+    pub fn is_multiplexed(&self) -> bool {
+        self.time_running < self.time_enabled
+    }
+
+    /// The event never got a hardware counter (or was never enabled), so no value is
+    /// available.
+    pub fn never_scheduled(&self) -> bool {
+        self.scaled.is_none()
+    }
+
+    /// The event was never enabled (e.g., the counter was not started), its value
+    /// is not a measurement.
+    pub fn never_enabled(&self) -> bool {
+        self.time_enabled == 0
+    }
 }
 
 #[derive(Debug)]
@@ -206,15 +335,92 @@ pub struct ReadRecord {
     pub value: ReadFormat,
 }
 
-#[derive(Debug)]
+/// The kind of a sampled branch (only filled in with `PERF_SAMPLE_BRANCH_TYPE_SAVE`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchType {
+    Unknown,
+    Conditional,
+    Unconditional,
+    Indirect,
+    Call,
+    IndirectCall,
+    Return,
+    Syscall,
+    SyscallReturn,
+    ConditionalCall,
+    ConditionalReturn,
+    ExceptionReturn,
+    Irq,
+    SError,
+    NoTx,
+    /// Architecture specific type (`new_type` of the entry), e.g., faults.
+    Extended(u8),
+}
+
+/// A branch of the branch stack (`struct perf_branch_entry`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BranchEntry {
     pub from: u64,
     pub to: u64,
+    /// Bit field: mispred:1, predicted:1, in_tx:1, abort:1, cycles:16, type:4,
+    /// spec:2, new_type:4, priv:3.
     pub flags: u64,
 }
 
+impl BranchEntry {
+    fn bits(&self, shift: u32, width: u32) -> u64 {
+        (self.flags >> shift) & ((1 << width) - 1)
+    }
+
+    /// The branch target was mispredicted.
+    pub fn mispred(&self) -> bool {
+        self.bits(0, 1) == 1
+    }
+
+    /// The branch target was predicted.
+    pub fn predicted(&self) -> bool {
+        self.bits(1, 1) == 1
+    }
+
+    /// The branch was in a transaction.
+    pub fn in_tx(&self) -> bool {
+        self.bits(2, 1) == 1
+    }
+
+    /// The branch is a transaction abort.
+    pub fn abort(&self) -> bool {
+        self.bits(3, 1) == 1
+    }
+
+    /// Cycles since the previous branch (0 if the hardware does not report it).
+    pub fn cycles(&self) -> u16 {
+        self.bits(4, 16) as u16
+    }
+
+    pub fn branch_type(&self) -> BranchType {
+        match self.bits(20, 4) {
+            0 => BranchType::Unknown,
+            1 => BranchType::Conditional,
+            2 => BranchType::Unconditional,
+            3 => BranchType::Indirect,
+            4 => BranchType::Call,
+            5 => BranchType::IndirectCall,
+            6 => BranchType::Return,
+            7 => BranchType::Syscall,
+            8 => BranchType::SyscallReturn,
+            9 => BranchType::ConditionalCall,
+            10 => BranchType::ConditionalReturn,
+            11 => BranchType::ExceptionReturn,
+            12 => BranchType::Irq,
+            13 => BranchType::SError,
+            14 => BranchType::NoTx,
+            _ => BranchType::Extended(self.bits(26, 4) as u8),
+        }
+    }
+}
+
 /// This record indicates a sample.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct SampleRecord {
     /// if PERF_SAMPLE_IDENTIFIER
     pub sample_id: Option<u64>,
@@ -240,11 +446,11 @@ pub struct SampleRecord {
     pub ips: Option<Vec<u64>>,
     /// if PERF_SAMPLE_RAW
     pub raw: Option<Vec<u8>>,
-    /// if PERF_SAMPLE_REGS_USER & PERF_SAMPLE_BRANCH_STACK
+    /// if PERF_SAMPLE_BRANCH_STACK
     pub lbr: Option<Vec<BranchEntry>>,
-    /// PERF_SAMPLE_STACK_USER
+    /// if PERF_SAMPLE_REGS_USER
     pub abi_user: Option<u64>,
-    /// PERF_SAMPLE_STACK_USER
+    /// if PERF_SAMPLE_REGS_USER (and `abi_user` is not `PERF_SAMPLE_REGS_ABI_NONE`)
     pub regs_user: Option<Vec<u64>>,
     /// PERF_SAMPLE_STACK_USER
     pub user_stack: Option<Vec<u8>>,
@@ -258,27 +464,61 @@ pub struct SampleRecord {
     pub transaction: Option<u64>,
     /// if PERF_SAMPLE_REGS_INTR
     pub abi: Option<u64>,
-    /// if PERF_SAMPLE_REGS_INTR
+    /// if PERF_SAMPLE_REGS_INTR (and `abi` is not `PERF_SAMPLE_REGS_ABI_NONE`)
     pub regs_intr: Option<Vec<u64>>,
 }
 
+impl SampleRecord {
+    /// The user mode registers by name, `attr` is the event the sample belongs to and
+    /// `arch` the architecture it was recorded on (e.g., from `PerfFile::get_reg_arch`).
+    ///
+    /// The names depend on the ABI of the sampled task, so a 32-bit task on x86_64
+    /// has no `R8`.
+    pub fn user_regs(
+        &self,
+        attr: &EventAttr,
+        arch: RegArch,
+    ) -> Option<BTreeMap<&'static str, u64>> {
+        let arch = arch.for_abi(self.abi_user?)?;
+        let values = self.regs_user.as_ref()?;
+        Some(arch.decode(attr.sample_regs_user, values))
+    }
+
+    /// The registers at the time of the overflow interrupt by name (see `user_regs`).
+    pub fn intr_regs(
+        &self,
+        attr: &EventAttr,
+        arch: RegArch,
+    ) -> Option<BTreeMap<&'static str, u64>> {
+        let arch = arch.for_abi(self.abi?)?;
+        let values = self.regs_intr.as_ref()?;
+        Some(arch.decode(attr.sample_regs_intr, values))
+    }
+}
+
 #[derive(Debug)]
 pub struct CommRecord {
     pub ptid: ThreadId,
     pub comm: String,
-    // TODO: sample_id
 }
 
 #[derive(Debug)]
 pub struct LostRecord {}
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BuildIdRecord {
     pub pid: i32,
     pub build_id: Vec<u8>,
     pub filename: String,
 }
 
+impl BuildIdRecord {
+    /// The build id as lower case hex string (like `perf buildid-list` prints it).
+    pub fn build_id_hex(&self) -> String {
+        super::buildid::build_id_hex(&self.build_id)
+    }
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum HeaderFlag {
     NrCpus,
@@ -412,19 +652,77 @@ pub struct EventAttr {
     pub config1_or_bp_addr: u64,
     pub config2_or_bp_len: u64,
 
-    pub branch_sample_type: u64,
+    pub branch_sample_type: BranchSampleType,
     pub sample_regs_user: u64,
     pub sample_stack_user: u32,
     pub clock_id: i32,
     pub sample_regs_intr: u64,
     pub aux_watermark: u32,
-    pub reserved: u32,
+    pub sample_max_stack: u16,
+    pub reserved_2: u16,
+    pub aux_sample_size: u32,
+    /// Bit field to pause/resume AUX tracing (since Linux 6.13, reserved before).
+    pub aux_action: u32,
+    pub sig_data: u64,
+    pub config3: u64,
 }
 
+/// Size of the first published `perf_event_attr` (up to `config1`).
+pub const PERF_ATTR_SIZE_VER0: u32 = 64;
+/// Adds `config2`.
+pub const PERF_ATTR_SIZE_VER1: u32 = 72;
+/// Adds `branch_sample_type`.
+pub const PERF_ATTR_SIZE_VER2: u32 = 80;
+/// Adds `sample_regs_user`, `sample_stack_user` and `clock_id`.
+pub const PERF_ATTR_SIZE_VER3: u32 = 96;
+/// Adds `sample_regs_intr`.
+pub const PERF_ATTR_SIZE_VER4: u32 = 104;
+/// Adds `aux_watermark` and `sample_max_stack`.
+pub const PERF_ATTR_SIZE_VER5: u32 = 112;
+/// Adds `aux_sample_size`.
+pub const PERF_ATTR_SIZE_VER6: u32 = 120;
+/// Adds `sig_data`.
+pub const PERF_ATTR_SIZE_VER7: u32 = 128;
+/// Adds `config3`, this is the size of `EventAttr`.
+pub const PERF_ATTR_SIZE_VER8: u32 = 136;
+
 impl EventAttr {
     pub fn attr_type(&self) -> EventAttrType {
         EventAttrType::new(self.attr_type)
     }
+
+    /// The fields that are set but not (completely) within the first `size` bytes,
+    /// i.e., the ones a kernel that supports only `size` bytes does not know.
+    pub fn fields_after(&self, size: u32) -> Vec<&'static str> {
+        // Name, end offset and if the field is set:
+        let fields = [
+            ("config2", PERF_ATTR_SIZE_VER1, self.config2_or_bp_len != 0),
+            (
+                "branch_sample_type",
+                PERF_ATTR_SIZE_VER2,
+                !self.branch_sample_type.is_empty(),
+            ),
+            ("sample_regs_user", 88, self.sample_regs_user != 0),
+            ("sample_stack_user", 92, self.sample_stack_user != 0),
+            ("clockid", PERF_ATTR_SIZE_VER3, self.clock_id != 0),
+            (
+                "sample_regs_intr",
+                PERF_ATTR_SIZE_VER4,
+                self.sample_regs_intr != 0,
+            ),
+            ("aux_watermark", 108, self.aux_watermark != 0),
+            ("sample_max_stack", 110, self.sample_max_stack != 0),
+            ("aux_sample_size", 116, self.aux_sample_size != 0),
+            ("aux_action", PERF_ATTR_SIZE_VER6, self.aux_action != 0),
+            ("sig_data", PERF_ATTR_SIZE_VER7, self.sig_data != 0),
+            ("config3", PERF_ATTR_SIZE_VER8, self.config3 != 0),
+        ];
+        fields
+            .iter()
+            .filter(|(_, end, set)| *set && *end > size)
+            .map(|(name, _, _)| *name)
+            .collect()
+    }
 }
 
 impl Default for EventAttr {
@@ -434,7 +732,7 @@ impl Default for EventAttr {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum EventAttrType {
     Hardware,
     Software,
@@ -471,6 +769,8 @@ bitflags! {
         const FORMAT_ID = 1 << 2;
         /// Allows all counter values in an event group to be read with one read.
         const FORMAT_GROUP = 1 << 3;
+        /// Adds the 64-bit number of lost samples for the event. (Since Linux 6.0)
+        const FORMAT_LOST = 1 << 4;
     }
 }
 
@@ -490,6 +790,10 @@ impl ReadFormatFlags {
     pub fn has_group(&self) -> bool {
         self.contains(ReadFormatFlags::FORMAT_GROUP)
     }
+
+    pub fn has_lost(&self) -> bool {
+        self.contains(ReadFormatFlags::FORMAT_LOST)
+    }
 }
 
 // Generated by using `cat /usr/include/linux/perf_event.h | grep PERF_SAMPLE_`
@@ -539,6 +843,19 @@ bitflags! {
 }
 
 impl SampleFormatFlags {
+    /// Size of the `SampleId` appended to non-sample records (with `sample_id_all`).
+    pub fn sample_id_size(&self) -> usize {
+        let fields = [
+            self.has_tid(),
+            self.has_time(),
+            self.has_sample_id(),
+            self.has_stream_id(),
+            self.has_cpu(),
+            self.has_identifier(),
+        ];
+        fields.iter().filter(|present| **present).count() * 8
+    }
+
     pub fn has_ip(&self) -> bool {
         self.contains(SampleFormatFlags::PERF_SAMPLE_IP)
     }
@@ -672,6 +989,98 @@ bitflags! {
         const EVENT_ATTR_EXCLUDE_CALLCHAIN_USER = 1 << 22;
         /// include mmap with inode data
         const EVENT_ATTR_MMAP2  =  1 << 23;
+        /// flag comm events that are due to an exec
+        const EVENT_ATTR_COMM_EXEC = 1 << 24;
+        /// use clock_id for time fields
+        const EVENT_ATTR_USE_CLOCKID = 1 << 25;
+        /// context switch data
+        const EVENT_ATTR_CONTEXT_SWITCH = 1 << 26;
+        /// write ring buffer from end to beginning
+        const EVENT_ATTR_WRITE_BACKWARD = 1 << 27;
+        /// include namespaces data
+        const EVENT_ATTR_NAMESPACES = 1 << 28;
+        /// include ksymbol events
+        const EVENT_ATTR_KSYMBOL = 1 << 29;
+        /// include bpf events
+        const EVENT_ATTR_BPF_EVENT = 1 << 30;
+        /// generate AUX records instead of events
+        const EVENT_ATTR_AUX_OUTPUT = 1 << 31;
+        /// include cgroup events
+        const EVENT_ATTR_CGROUP = 1 << 32;
+        /// include text poke events
+        const EVENT_ATTR_TEXT_POKE = 1 << 33;
+        /// use build id in mmap2 events
+        const EVENT_ATTR_BUILD_ID = 1 << 34;
+        /// children only inherit if cloned with CLONE_THREAD
+        const EVENT_ATTR_INHERIT_THREAD = 1 << 35;
+        /// event is removed from task on exec
+        const EVENT_ATTR_REMOVE_ON_EXEC = 1 << 36;
+        /// send synchronous SIGTRAP on event
+        const EVENT_ATTR_SIGTRAP = 1 << 37;
+    }
+}
+
+bitflags! {
+    /// Which branches to record with `PERF_SAMPLE_BRANCH_STACK`.
+    pub struct BranchSampleType: u64 {
+        /// user branches
+        const PERF_SAMPLE_BRANCH_USER = perf_event::PERF_SAMPLE_BRANCH_USER as u64;
+        /// kernel branches
+        const PERF_SAMPLE_BRANCH_KERNEL = perf_event::PERF_SAMPLE_BRANCH_KERNEL as u64;
+        /// hypervisor branches
+        const PERF_SAMPLE_BRANCH_HV = perf_event::PERF_SAMPLE_BRANCH_HV as u64;
+        /// any branch types
+        const PERF_SAMPLE_BRANCH_ANY = perf_event::PERF_SAMPLE_BRANCH_ANY as u64;
+        /// any call branch
+        const PERF_SAMPLE_BRANCH_ANY_CALL = perf_event::PERF_SAMPLE_BRANCH_ANY_CALL as u64;
+        /// any return branch
+        const PERF_SAMPLE_BRANCH_ANY_RETURN = perf_event::PERF_SAMPLE_BRANCH_ANY_RETURN as u64;
+        /// indirect calls
+        const PERF_SAMPLE_BRANCH_IND_CALL = perf_event::PERF_SAMPLE_BRANCH_IND_CALL as u64;
+        /// transaction aborts
+        const PERF_SAMPLE_BRANCH_ABORT_TX = perf_event::PERF_SAMPLE_BRANCH_ABORT_TX as u64;
+        /// in transaction
+        const PERF_SAMPLE_BRANCH_IN_TX = perf_event::PERF_SAMPLE_BRANCH_IN_TX as u64;
+        /// not in transaction
+        const PERF_SAMPLE_BRANCH_NO_TX = perf_event::PERF_SAMPLE_BRANCH_NO_TX as u64;
+        /// conditional branches
+        const PERF_SAMPLE_BRANCH_COND = perf_event::PERF_SAMPLE_BRANCH_COND as u64;
+        /// call/ret stack
+        const PERF_SAMPLE_BRANCH_CALL_STACK = perf_event::PERF_SAMPLE_BRANCH_CALL_STACK as u64;
+        /// indirect jumps
+        const PERF_SAMPLE_BRANCH_IND_JUMP = perf_event::PERF_SAMPLE_BRANCH_IND_JUMP as u64;
+        /// direct call
+        const PERF_SAMPLE_BRANCH_CALL = perf_event::PERF_SAMPLE_BRANCH_CALL as u64;
+        /// no flags
+        const PERF_SAMPLE_BRANCH_NO_FLAGS = perf_event::PERF_SAMPLE_BRANCH_NO_FLAGS as u64;
+        /// no cycles
+        const PERF_SAMPLE_BRANCH_NO_CYCLES = perf_event::PERF_SAMPLE_BRANCH_NO_CYCLES as u64;
+        /// save branch type
+        const PERF_SAMPLE_BRANCH_TYPE_SAVE = perf_event::PERF_SAMPLE_BRANCH_TYPE_SAVE as u64;
+        /// save low level index of raw branch records
+        const PERF_SAMPLE_BRANCH_HW_INDEX = perf_event::PERF_SAMPLE_BRANCH_HW_INDEX as u64;
+        /// save privilege mode
+        const PERF_SAMPLE_BRANCH_PRIV_SAVE = perf_event::PERF_SAMPLE_BRANCH_PRIV_SAVE as u64;
+        /// save occurrences of events on a branch
+        const PERF_SAMPLE_BRANCH_COUNTERS = perf_event::PERF_SAMPLE_BRANCH_COUNTERS as u64;
+    }
+}
+
+impl BranchSampleType {
+    /// The privilege level bits (user, kernel, hypervisor).
+    pub fn privilege_levels(&self) -> BranchSampleType {
+        *self
+            & (BranchSampleType::PERF_SAMPLE_BRANCH_USER
+                | BranchSampleType::PERF_SAMPLE_BRANCH_KERNEL
+                | BranchSampleType::PERF_SAMPLE_BRANCH_HV)
+    }
+
+    pub fn has_hw_index(&self) -> bool {
+        self.contains(BranchSampleType::PERF_SAMPLE_BRANCH_HW_INDEX)
+    }
+
+    pub fn has_counters(&self) -> bool {
+        self.contains(BranchSampleType::PERF_SAMPLE_BRANCH_COUNTERS)
     }
 }
 
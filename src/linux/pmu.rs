@@ -0,0 +1,369 @@
+//! Discover the PMUs the kernel exposes in `/sys/bus/event_source/devices`.
+//!
+//! Besides the generic event types (hardware, software, tracepoint etc.) every
+//! PMU (core, uncore, power etc.) registers itself with a dynamic type number.
+//! Its directory describes how the bits of `config`, `config1`, `config2` and
+//! `config3` are laid out (`format/`) and lists named events (`events/`).
+//!
+//! # Example
+//! ```no_run
+//! use perfcnt::linux::pmu::PmuRegistry;
+//! use perfcnt::linux::PerfCounterBuilderLinux;
+//!
+//! let pmus = PmuRegistry::new().expect("Can not read PMUs");
+//! let attr = pmus
+//!     .event_attr("uncore_imc_0", "event=0x04,umask=0x03")
+//!     .expect("Invalid event");
+//! let pc = PerfCounterBuilderLinux::from_event_attr(&attr)
+//!     .on_cpu(0)
+//!     .for_all_pids()
+//!     .finish();
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::io::Error;
+use std::path::Path;
+
+use super::percpu::parse_cpu_list;
+use super::perf_format::EventAttr;
+
+/// Location of the PMU devices relative to the sysfs root.
+const EVENT_SOURCE_DEVICES: &str = "bus/event_source/devices";
+
+fn invalid_input(msg: String) -> Error {
+    Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn invalid_data(msg: String) -> Error {
+    Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Parses a number as written in sysfs (decimal or hex with 0x prefix).
+fn parse_number(s: &str) -> Option<u64> {
+    let s = s.trim();
+    if s.starts_with("0x") || s.starts_with("0X") {
+        u64::from_str_radix(&s[2..], 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+/// The attribute field a format term is encoded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigField {
+    Config,
+    Config1,
+    Config2,
+    Config3,
+}
+
+impl ConfigField {
+    fn get_mut<'a>(&self, attr: &'a mut EventAttr) -> &'a mut u64 {
+        match *self {
+            ConfigField::Config => &mut attr.config,
+            ConfigField::Config1 => &mut attr.config1_or_bp_addr,
+            ConfigField::Config2 => &mut attr.config2_or_bp_len,
+            ConfigField::Config3 => &mut attr.config3,
+        }
+    }
+}
+
+/// Describes where the bits of a term go, e.g., `config:0-7,32-35`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatField {
+    /// The attribute field the value is stored in.
+    pub field: ConfigField,
+    /// Inclusive bit ranges (low, high), the low bits of a value go to the first range.
+    pub bits: Vec<(u32, u32)>,
+}
+
+impl FormatField {
+    /// Parses the contents of a file in the `format` directory of a PMU.
+    pub fn parse(desc: &str) -> Result<FormatField, io::Error> {
+        let invalid = || invalid_data(format!("Invalid format description '{}'", desc.trim()));
+
+        let mut parts = desc.trim().splitn(2, ':');
+        let field = match parts.next() {
+            Some("config") => ConfigField::Config,
+            Some("config1") => ConfigField::Config1,
+            Some("config2") => ConfigField::Config2,
+            Some("config3") => ConfigField::Config3,
+            _ => return Err(invalid()),
+        };
+
+        let mut bits = Vec::new();
+        for range in parts.next().ok_or_else(invalid)?.split(',') {
+            let mut ends = range.splitn(2, '-');
+            let low: u32 = ends
+                .next()
+                .and_then(|s| s.trim().parse().ok())
+                .ok_or_else(invalid)?;
+            let high: u32 = match ends.next() {
+                Some(s) => s.trim().parse().map_err(|_| invalid())?,
+                None => low,
+            };
+            if high < low || high > 63 {
+                return Err(invalid());
+            }
+            bits.push((low, high));
+        }
+
+        Ok(FormatField { field, bits })
+    }
+
+    /// Total number of bits available for a value.
+    pub fn width(&self) -> u32 {
+        self.bits.iter().map(|(low, high)| high - low + 1).sum()
+    }
+
+    /// Store `value` in the bits of `attr` this format describes.
+    ///
+    /// Fails if the value does not fit.
+    pub fn apply(&self, attr: &mut EventAttr, value: u64) -> Result<(), io::Error> {
+        let width = self.width();
+        if width < 64 && value >> width != 0 {
+            return Err(invalid_input(format!(
+                "Value {:#x} does not fit into {} bits",
+                value, width
+            )));
+        }
+
+        let config = self.field.get_mut(attr);
+        let mut value = value;
+        for &(low, high) in self.bits.iter() {
+            let len = high - low + 1;
+            let mask = if len == 64 { !0 } else { (1u64 << len) - 1 };
+            *config &= !(mask << low);
+            *config |= (value & mask) << low;
+            value = value.checked_shr(len).unwrap_or(0);
+        }
+        Ok(())
+    }
+}
+
+/// The names and contents of the files in `dir`, files that can not be read are
+/// noted in `skipped`.
+fn read_entries(dir: &Path, skipped: &mut Vec<String>) -> Vec<(String, String)> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                skipped.push(format!("{}: {}", dir.display(), e));
+                continue;
+            }
+        };
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match fs::read_to_string(&path) {
+            Ok(content) => files.push((name, content)),
+            Err(e) => skipped.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    files
+}
+
+/// A PMU as described in `/sys/bus/event_source/devices/<name>`.
+#[derive(Debug, Clone)]
+pub struct Pmu {
+    /// Name of the PMU (e.g., `cpu`, `uncore_imc_0`, `power`).
+    pub name: String,
+    /// The value for `EventAttr.attr_type`.
+    pub pmu_type: u32,
+    /// Named terms and where they are stored in the attributes.
+    pub formats: BTreeMap<String, FormatField>,
+    /// Event aliases, mapping a name to the terms it stands for.
+    pub events: BTreeMap<String, String>,
+    /// CPUs an event of this PMU should be opened on (typically for uncore PMUs).
+    pub cpus: Option<Vec<usize>>,
+    /// Why `format` or `events` entries that could not be read were left out.
+    pub skipped: Vec<String>,
+}
+
+impl Pmu {
+    /// Reads the description of a PMU from its sysfs directory.
+    pub fn from_dir(dir: &Path) -> Result<Pmu, io::Error> {
+        let name = dir
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| invalid_data(format!("Invalid PMU directory {}", dir.display())))?
+            .to_string();
+
+        let pmu_type = fs::read_to_string(dir.join("type"))?
+            .trim()
+            .parse()
+            .map_err(|_| invalid_data(format!("Invalid type for PMU '{}'", name)))?;
+
+        // A bad entry only makes that term or alias unavailable:
+        let mut skipped = Vec::new();
+        let mut formats = BTreeMap::new();
+        for (term, desc) in read_entries(&dir.join("format"), &mut skipped) {
+            match FormatField::parse(&desc) {
+                Ok(format) => {
+                    formats.insert(term, format);
+                }
+                Err(e) => skipped.push(format!("{}/format/{}: {}", name, term, e)),
+            }
+        }
+
+        let mut events = BTreeMap::new();
+        for (alias, terms) in read_entries(&dir.join("events"), &mut skipped) {
+            // Skip the meta-data files (.scale, .unit, .snapshot etc.):
+            if !alias.contains('.') {
+                events.insert(alias, terms.trim().to_string());
+            }
+        }
+
+        let cpus = match fs::read_to_string(dir.join("cpumask")) {
+            Ok(list) => Some(parse_cpu_list(&list)?),
+            Err(_) => None,
+        };
+
+        Ok(Pmu {
+            name,
+            pmu_type,
+            formats,
+            events,
+            cpus,
+            skipped,
+        })
+    }
+
+    /// Builds the attributes for an event of this PMU.
+    ///
+    /// `terms` is a comma separated list of `term=value` pairs (a term without a value
+    /// is set to 1) or event aliases, e.g., `event=0x04,umask=0x03` or `cas_count_read`.
+    /// Besides the terms in `formats`, `config`, `config1`, `config2` and `config3` set
+    /// the raw fields.
+    pub fn event_attr(&self, terms: &str) -> Result<EventAttr, io::Error> {
+        let mut attr = EventAttr {
+            attr_type: self.pmu_type,
+            ..Default::default()
+        };
+        self.apply_terms(&mut attr, terms, true)?;
+        Ok(attr)
+    }
+
+    fn apply_terms(
+        &self,
+        attr: &mut EventAttr,
+        terms: &str,
+        allow_alias: bool,
+    ) -> Result<(), io::Error> {
+        for term in terms.split(',').map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            let mut kv = term.splitn(2, '=');
+            let name = kv.next().unwrap_or("").trim();
+            let value = match kv.next() {
+                Some(v) => parse_number(v).ok_or_else(|| {
+                    invalid_input(format!(
+                        "Invalid value in term '{}' for PMU '{}'",
+                        term, self.name
+                    ))
+                })?,
+                None => {
+                    if allow_alias {
+                        if let Some(alias) = self.events.get(name) {
+                            // Aliases are defined in terms of formats, they don't nest:
+                            self.apply_terms(attr, alias, false)?;
+                            continue;
+                        }
+                    }
+                    1
+                }
+            };
+
+            match self.formats.get(name) {
+                Some(format) => format.apply(attr, value)?,
+                None => match name {
+                    "config" => attr.config = value,
+                    "config1" => attr.config1_or_bp_addr = value,
+                    "config2" => attr.config2_or_bp_len = value,
+                    "config3" => attr.config3 = value,
+                    _ => {
+                        return Err(invalid_input(format!(
+                            "Unknown term '{}' for PMU '{}'",
+                            name, self.name
+                        )))
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+/// All PMUs registered with the kernel.
+#[derive(Debug, Clone)]
+pub struct PmuRegistry {
+    pmus: BTreeMap<String, Pmu>,
+    skipped: Vec<String>,
+}
+
+impl PmuRegistry {
+    /// Reads the PMUs from `/sys`.
+    pub fn new() -> Result<PmuRegistry, io::Error> {
+        PmuRegistry::with_sysfs_root(Path::new("/sys"))
+    }
+
+    /// Reads the PMUs from a sysfs tree mounted at `sysfs_root`.
+    ///
+    /// Fails only if the PMU directory can not be listed, PMUs (and entries of PMUs)
+    /// that can not be read are left out, see `skipped`.
+    pub fn with_sysfs_root(sysfs_root: &Path) -> Result<PmuRegistry, io::Error> {
+        let mut pmus = BTreeMap::new();
+        let mut skipped = Vec::new();
+        for entry in fs::read_dir(sysfs_root.join(EVENT_SOURCE_DEVICES))? {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    skipped.push(e.to_string());
+                    continue;
+                }
+            };
+            match Pmu::from_dir(&path) {
+                Ok(pmu) => {
+                    skipped.extend(pmu.skipped.iter().cloned());
+                    pmus.insert(pmu.name.clone(), pmu);
+                }
+                Err(e) => skipped.push(format!("{}: {}", path.display(), e)),
+            }
+        }
+        Ok(PmuRegistry { pmus, skipped })
+    }
+
+    /// Why PMUs or their entries were left out when reading them (e.g., a format the
+    /// parser does not understand), empty if all could be read.
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
+    }
+
+    /// Find a PMU by its name.
+    pub fn get(&self, name: &str) -> Option<&Pmu> {
+        self.pmus.get(name)
+    }
+
+    /// All PMUs, sorted by name.
+    pub fn pmus(&self) -> impl Iterator<Item = &Pmu> {
+        self.pmus.values()
+    }
+
+    /// Builds the attributes for an event of PMU `pmu`, see `Pmu::event_attr`.
+    pub fn event_attr(&self, pmu: &str, terms: &str) -> Result<EventAttr, io::Error> {
+        self.get(pmu)
+            .ok_or_else(|| {
+                Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("PMU '{}' does not exist", pmu),
+                )
+            })?
+            .event_attr(terms)
+    }
+}
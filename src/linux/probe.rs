@@ -0,0 +1,180 @@
+//! Find out what this machine allows before measuring anything.
+//!
+//! `SystemProbe::new` reads the kernel settings that restrict perf events, tries to
+//! open every generic hardware and cache event and lists the PMUs. Tools can use it
+//! to skip unsupported events or to adjust their counters (see `restrict`).
+//!
+//! # Example
+//! ```no_run
+//! use perfcnt::linux::probe::SystemProbe;
+//! use perfcnt::linux::{HardwareEventType, PerfCounterBuilderLinux};
+//!
+//! let probe = SystemProbe::new();
+//! for event in probe.hardware_events.iter().filter(|e| e.is_supported()) {
+//!     println!("{} is available", event.name);
+//! }
+//!
+//! let mut builder = PerfCounterBuilderLinux::from_hardware_event(HardwareEventType::Instructions);
+//! // Counts user mode only if the kernel does not allow more:
+//! let pc = probe.restrict(&mut builder).finish();
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use super::error::{PerfError, PerfPermissions};
+use super::event_str::{format_event, CACHES, CACHE_OPS, HARDWARE_EVENTS};
+use super::pmu::PmuRegistry;
+use super::{CacheOpResultId, PerfCounterBuilderLinux};
+
+/// Locations relative to the procfs root.
+const PERF_EVENT_MAX_SAMPLE_RATE: &str = "sys/kernel/perf_event_max_sample_rate";
+const PERF_EVENT_MLOCK_KB: &str = "sys/kernel/perf_event_mlock_kb";
+const PERF_USER_ACCESS: &str = "sys/kernel/perf_user_access";
+
+/// Location of the rdpmc setting of the x86 core PMU relative to the sysfs root.
+const CPU_RDPMC: &str = "bus/event_source/devices/cpu/rdpmc";
+
+fn read_number<T: std::str::FromStr>(path: &Path) -> Option<T> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Whether an event could be opened.
+#[derive(Debug)]
+pub struct EventSupport {
+    /// The perf name of the event (e.g., `cycles` or `L1-dcache-load-misses`).
+    pub name: String,
+    /// Why the event could not be opened, `None` if it could.
+    pub error: Option<PerfError>,
+}
+
+impl EventSupport {
+    pub fn is_supported(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// What this machine allows with perf events.
+#[derive(Debug)]
+pub struct SystemProbe {
+    /// perf_event_paranoid and the capabilities of the process.
+    pub permissions: PerfPermissions,
+    /// `kernel.perf_event_max_sample_rate`, the highest sampling frequency.
+    pub max_sample_rate: Option<u64>,
+    /// `kernel.perf_event_mlock_kb`, how much ring buffer memory an unprivileged user may lock.
+    pub mlock_kb: Option<u64>,
+    /// The rdpmc setting of the core PMU (x86 `rdpmc` in sysfs, aarch64 `perf_user_access`):
+    /// 0 = disabled, 1 = for processes with a mapped counter, 2 = always.
+    pub rdpmc: Option<u32>,
+    /// The generic hardware events (one entry per event, without aliases).
+    pub hardware_events: Vec<EventSupport>,
+    /// The generic cache events.
+    pub cache_events: Vec<EventSupport>,
+    /// Names of the PMUs registered with the kernel.
+    pub pmus: Vec<String>,
+    /// Why PMUs (or some of their formats and events) could not be read.
+    pub pmu_errors: Vec<String>,
+}
+
+impl Default for SystemProbe {
+    fn default() -> SystemProbe {
+        SystemProbe::new()
+    }
+}
+
+impl SystemProbe {
+    /// Reads the settings of this machine and tries to open all generic events.
+    pub fn new() -> SystemProbe {
+        let mut probe = SystemProbe::read_settings(Path::new("/proc"), Path::new("/sys"));
+        probe.probe_events();
+        probe
+    }
+
+    /// Only reads the settings from a procfs at `proc_root` and sysfs at `sysfs_root`,
+    /// the event lists stay empty.
+    pub fn read_settings(proc_root: &Path, sysfs_root: &Path) -> SystemProbe {
+        let rdpmc = read_number(&sysfs_root.join(CPU_RDPMC))
+            .or_else(|| read_number(&proc_root.join(PERF_USER_ACCESS)));
+        let (pmus, pmu_errors) = match PmuRegistry::with_sysfs_root(sysfs_root) {
+            Ok(registry) => (
+                registry.pmus().map(|p| p.name.clone()).collect(),
+                registry.skipped().to_vec(),
+            ),
+            Err(e) => (Vec::new(), vec![e.to_string()]),
+        };
+
+        SystemProbe {
+            permissions: PerfPermissions::from_proc(proc_root),
+            max_sample_rate: read_number(&proc_root.join(PERF_EVENT_MAX_SAMPLE_RATE)),
+            mlock_kb: read_number(&proc_root.join(PERF_EVENT_MLOCK_KB)),
+            rdpmc,
+            hardware_events: Vec::new(),
+            cache_events: Vec::new(),
+            pmus,
+            pmu_errors,
+        }
+    }
+
+    /// Tries to open every generic hardware and cache event for this process.
+    ///
+    /// The events are opened with the restrictions of `restrict` applied.
+    pub fn probe_events(&mut self) {
+        let mut hardware_events = Vec::new();
+        let mut seen = Vec::new();
+        for (_, event) in HARDWARE_EVENTS.iter() {
+            // Skip the aliases (cpu-cycles, branches etc.):
+            if seen.contains(&(*event as u64)) {
+                continue;
+            }
+            seen.push(*event as u64);
+            let builder = PerfCounterBuilderLinux::from_hardware_event(*event);
+            hardware_events.push(self.try_open(&builder));
+        }
+        self.hardware_events = hardware_events;
+
+        let mut cache_events = Vec::new();
+        for (_, cache) in CACHES.iter() {
+            for (_, _, op) in CACHE_OPS.iter() {
+                for result in [CacheOpResultId::Access, CacheOpResultId::Miss].iter() {
+                    let builder = PerfCounterBuilderLinux::from_cache_event(*cache, *op, *result);
+                    cache_events.push(self.try_open(&builder));
+                }
+            }
+        }
+        self.cache_events = cache_events;
+    }
+
+    fn try_open(&self, builder: &PerfCounterBuilderLinux) -> EventSupport {
+        let name = format_event(builder.attributes(), None);
+        let mut builder = builder.clone();
+        let error = self.restrict(&mut builder).finish().err();
+        EventSupport { name, error }
+    }
+
+    /// Can this process count events in kernel mode?
+    pub fn kernel_allowed(&self) -> bool {
+        self.permissions.is_privileged() || matches!(self.permissions.paranoid, Some(p) if p <= 1)
+    }
+
+    /// Can this process count all processes on a CPU?
+    pub fn system_wide_allowed(&self) -> bool {
+        self.permissions.is_privileged() || matches!(self.permissions.paranoid, Some(p) if p <= 0)
+    }
+
+    /// Can counters be read with `rdpmc` from user space?
+    pub fn rdpmc_allowed(&self) -> bool {
+        matches!(self.rdpmc, Some(r) if r > 0)
+    }
+
+    /// Adjust `builder` to what this machine allows: if counting in kernel mode is not
+    /// permitted, only user mode is counted.
+    pub fn restrict<'a>(
+        &self,
+        builder: &'a mut PerfCounterBuilderLinux,
+    ) -> &'a mut PerfCounterBuilderLinux {
+        if !self.kernel_allowed() {
+            builder.exclude_kernel().exclude_hv();
+        }
+        builder
+    }
+}
@@ -0,0 +1,266 @@
+//! Reconstructs what every process had mapped, and what it was called, over time.
+//!
+//! The side-band records of a recording describe the processes: `MMAP`/`MMAP2` add
+//! mappings, `FORK` copies the address space of the parent, `COMM` with
+//! `PERF_RECORD_MISC_COMM_EXEC` (an exec) replaces it and `EXIT` ends it. The
+//! `ProcessTracker` consumes the events in order and keeps the history, so a sample
+//! can be attributed to the file that was mapped at its address when it was taken.
+//!
+//! Open the counters with `sample_id_all` and `PERF_SAMPLE_TIME` (and `comm_exec`),
+//! otherwise MMAP and COMM records have no timestamp and are assumed to happen at
+//! the time of the previous event.
+//!
+//! # Example
+//! ```no_run
+//! use perfcnt::linux::perf_file::PerfFile;
+//! use perfcnt::linux::perf_format::EventData;
+//! use perfcnt::linux::process::ProcessTracker;
+//!
+//! let pf = PerfFile::new(std::fs::read("perf.data").expect("Can not read perf.data"));
+//! let mut tracker = ProcessTracker::new();
+//! for event in pf.data() {
+//!     tracker.process_event(&event);
+//!     if let EventData::Sample(ref sample) = event.data {
+//!         let (pid, time, ip) = (sample.ptid.as_ref().unwrap().pid, sample.time.unwrap(), sample.ip.unwrap());
+//!         println!("{:?} in {:?}", tracker.comm(pid, time), tracker.lookup(pid, time, ip));
+//!     }
+//! }
+//! ```
+
+use std::collections::HashMap;
+
+use super::maps::{Mapping, Maps};
+use super::perf_format::{Event, EventData};
+
+/// Mappings of the kernel and its modules are reported for this pid.
+const KERNEL_PID: i32 = -1;
+
+/// A mapping and the time it was valid, `[start, end)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TimedMapping {
+    mapping: Mapping,
+    start: u64,
+    /// `None` while it is still mapped.
+    end: Option<u64>,
+}
+
+impl TimedMapping {
+    fn valid_at(&self, time: u64) -> bool {
+        self.start <= time && !matches!(self.end, Some(end) if end <= time)
+    }
+}
+
+/// What is known about a process.
+#[derive(Debug, Clone, Default)]
+pub struct Process {
+    pub pid: i32,
+    /// The process that forked this one (if the fork was recorded).
+    pub ppid: Option<i32>,
+    /// The names of the process and since when they apply, in order.
+    pub comms: Vec<(u64, String)>,
+    /// When the process was forked (if the fork was recorded).
+    pub start_time: Option<u64>,
+    /// When the process exited.
+    pub exit_time: Option<u64>,
+    mappings: Vec<TimedMapping>,
+}
+
+impl Process {
+    fn new(pid: i32) -> Process {
+        Process {
+            pid,
+            ..Default::default()
+        }
+    }
+
+    /// The name of the process at `time`.
+    pub fn comm(&self, time: u64) -> Option<&str> {
+        self.comms
+            .iter()
+            .rev()
+            .find(|(since, _)| *since <= time)
+            .or_else(|| self.comms.first())
+            .map(|(_, comm)| comm.as_str())
+    }
+
+    /// The mapping that contained `addr` at `time`.
+    pub fn lookup(&self, time: u64, addr: u64) -> Option<&Mapping> {
+        self.mappings
+            .iter()
+            .rev()
+            .find(|m| m.mapping.contains(addr) && m.valid_at(time))
+            .map(|m| &m.mapping)
+    }
+
+    /// All mappings of the process at `time`.
+    pub fn maps_at(&self, time: u64) -> Maps {
+        let mut maps = Maps::new();
+        for m in self.mappings.iter().filter(|m| m.valid_at(time)) {
+            maps.insert(m.mapping.clone());
+        }
+        maps
+    }
+
+    /// Adds `mapping` at `time`, it replaces what was mapped in its range.
+    fn map(&mut self, time: u64, mapping: Mapping) {
+        let mut remainders = Vec::new();
+        for old in self.mappings.iter_mut().filter(|m| m.end.is_none()) {
+            if old.mapping.end <= mapping.start || old.mapping.start >= mapping.end {
+                continue;
+            }
+            old.end = Some(time);
+            // The parts of the old mapping outside of the new one stay mapped:
+            let mut maps = Maps::new();
+            maps.insert(old.mapping.clone());
+            maps.insert(mapping.clone());
+            remainders.extend(maps.iter().filter(|m| **m != mapping).cloned());
+        }
+        for remainder in remainders {
+            self.mappings.push(TimedMapping {
+                mapping: remainder,
+                start: time,
+                end: None,
+            });
+        }
+        self.mappings.push(TimedMapping {
+            mapping,
+            start: time,
+            end: None,
+        });
+    }
+
+    /// Everything that is still mapped goes away at `time` (exec or exit).
+    fn unmap_all(&mut self, time: u64) {
+        for m in self.mappings.iter_mut().filter(|m| m.end.is_none()) {
+            m.end = Some(time);
+        }
+    }
+}
+
+/// Follows the processes of a recording.
+#[derive(Debug, Default)]
+pub struct ProcessTracker {
+    processes: HashMap<i32, Process>,
+    /// Time of the latest event with a timestamp.
+    time: u64,
+}
+
+impl ProcessTracker {
+    pub fn new() -> ProcessTracker {
+        Default::default()
+    }
+
+    /// Updates the processes with `event`, events must be passed in the order
+    /// they were recorded.
+    pub fn process_event(&mut self, event: &Event) {
+        if let Some(time) = event.time() {
+            self.time = time;
+        }
+        let time = self.time;
+
+        match event.data {
+            EventData::MMAP(ref mmap) => self.process(mmap.pid).map(time, mmap.into()),
+            EventData::MMAP2(ref mmap) => self.process(mmap.ptid.pid).map(time, mmap.into()),
+            EventData::Comm(ref comm) => {
+                let process = self.process(comm.ptid.pid);
+                if event.header.is_comm_exec() {
+                    process.unmap_all(time);
+                }
+                // Threads get names too, only the main thread names the process:
+                if comm.ptid.pid == comm.ptid.tid || process.comms.is_empty() {
+                    process.comms.push((time, comm.comm.clone()));
+                }
+            }
+            EventData::Fork(ref fork) => {
+                // A new thread shares the address space:
+                if fork.pid == fork.ppid {
+                    return;
+                }
+                let (pid, ppid) = (fork.pid as i32, fork.ppid as i32);
+                let (comms, mappings) = match self.processes.get(&ppid) {
+                    Some(parent) => (
+                        parent
+                            .comm(time)
+                            .map(|c| vec![(time, String::from(c))])
+                            .unwrap_or_default(),
+                        parent.maps_at(time),
+                    ),
+                    None => (Vec::new(), Maps::new()),
+                };
+
+                let mut child = Process::new(pid);
+                child.ppid = Some(ppid);
+                child.start_time = Some(time);
+                child.comms = comms;
+                for mapping in mappings.iter() {
+                    child.map(time, mapping.clone());
+                }
+                // A reused pid starts over, the old mappings end with the fork:
+                if let Some(old) = self.processes.get(&pid) {
+                    child.comms.splice(0..0, old.comms.iter().cloned());
+                    child.mappings.splice(
+                        0..0,
+                        old.mappings.iter().cloned().map(|mut m| {
+                            m.end = Some(m.end.map_or(time, |end| end.min(time)));
+                            m
+                        }),
+                    );
+                }
+                self.processes.insert(pid, child);
+            }
+            // Only the exit of the main thread ends the process:
+            EventData::Exit(ref exit) if exit.pid == exit.tid => {
+                let process = self.process(exit.pid as i32);
+                process.unmap_all(time);
+                process.exit_time = Some(time);
+            }
+            _ => {}
+        }
+    }
+
+    fn process(&mut self, pid: i32) -> &mut Process {
+        self.processes
+            .entry(pid)
+            .or_insert_with(|| Process::new(pid))
+    }
+
+    /// What is known about process `pid`.
+    pub fn get(&self, pid: i32) -> Option<&Process> {
+        self.processes.get(&pid)
+    }
+
+    /// The pids of all processes seen so far.
+    pub fn pids(&self) -> impl Iterator<Item = i32> + '_ {
+        self.processes.keys().cloned()
+    }
+
+    /// The mapping that contained `addr` in process `pid` at `time`, kernel addresses
+    /// are looked up in the mappings of the kernel.
+    pub fn lookup(&self, pid: i32, time: u64, addr: u64) -> Option<&Mapping> {
+        self.processes
+            .get(&pid)
+            .and_then(|p| p.lookup(time, addr))
+            .or_else(|| {
+                self.processes
+                    .get(&KERNEL_PID)
+                    .and_then(|p| p.lookup(time, addr))
+            })
+    }
+
+    /// All mappings of process `pid` at `time` (e.g., for the `Unwinder`).
+    pub fn maps_at(&self, pid: i32, time: u64) -> Maps {
+        self.processes
+            .get(&pid)
+            .map_or_else(Maps::new, |p| p.maps_at(time))
+    }
+
+    /// The name of process `pid` at `time`.
+    pub fn comm(&self, pid: i32, time: u64) -> Option<&str> {
+        self.processes.get(&pid).and_then(|p| p.comm(time))
+    }
+
+    /// The process that forked `pid`.
+    pub fn parent(&self, pid: i32) -> Option<i32> {
+        self.processes.get(&pid).and_then(|p| p.ppid)
+    }
+}
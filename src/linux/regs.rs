@@ -0,0 +1,227 @@
+//! Registers that can be sampled with `PERF_SAMPLE_REGS_USER` and `PERF_SAMPLE_REGS_INTR`.
+//!
+//! The kernel selects registers with a bit mask (`sample_regs_user`, `sample_regs_intr`)
+//! whose bits are the indices of `arch/*/include/uapi/asm/perf_regs.h`, and a sample
+//! contains one value for every bit set, in ascending bit order. Which register a bit
+//! stands for depends on the architecture and on the ABI of the sampled task (a 32-bit
+//! process on x86_64 has no R8 - R15).
+//!
+//! # Example
+//! ```no_run
+//! use perfcnt::linux::regs::Reg;
+//! use perfcnt::linux::{HardwareEventType, PerfCounterBuilderLinux};
+//!
+//! let mut pc = PerfCounterBuilderLinux::from_hardware_event(HardwareEventType::CPUCycles);
+//! # #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+//! pc.sample_user_regs(&[Reg::Ip, Reg::Sp, Reg::Bp]);
+//! ```
+
+use std::collections::BTreeMap;
+
+use super::perf_event;
+
+/// A register that can be selected in a sample register mask.
+pub trait Register: Copy {
+    /// The bit of the register in the mask.
+    fn index(self) -> u8;
+
+    /// The name perf uses for the register.
+    fn name(self) -> &'static str;
+}
+
+/// Builds the mask for `sample_regs_user` or `sample_regs_intr`.
+pub fn regs_mask<R: Register>(regs: &[R]) -> u64 {
+    regs.iter().fold(0, |mask, r| mask | 1 << r.index())
+}
+
+/// Registers of x86 and x86_64 (`enum perf_event_x86_regs`).
+///
+/// `R8` - `R15` only exist on x86_64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum X86Reg {
+    Ax = 0,
+    Bx = 1,
+    Cx = 2,
+    Dx = 3,
+    Si = 4,
+    Di = 5,
+    Bp = 6,
+    Sp = 7,
+    Ip = 8,
+    Flags = 9,
+    Cs = 10,
+    Ss = 11,
+    Ds = 12,
+    Es = 13,
+    Fs = 14,
+    Gs = 15,
+    R8 = 16,
+    R9 = 17,
+    R10 = 18,
+    R11 = 19,
+    R12 = 20,
+    R13 = 21,
+    R14 = 22,
+    R15 = 23,
+}
+
+static X86_REG_NAMES: [&str; 24] = [
+    "AX", "BX", "CX", "DX", "SI", "DI", "BP", "SP", "IP", "FLAGS", "CS", "SS", "DS", "ES", "FS",
+    "GS", "R8", "R9", "R10", "R11", "R12", "R13", "R14", "R15",
+];
+
+impl Register for X86Reg {
+    fn index(self) -> u8 {
+        self as u8
+    }
+
+    fn name(self) -> &'static str {
+        X86_REG_NAMES[self as usize]
+    }
+}
+
+/// Registers of aarch64 (`enum perf_event_arm_regs`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Aarch64Reg {
+    X0 = 0,
+    X1 = 1,
+    X2 = 2,
+    X3 = 3,
+    X4 = 4,
+    X5 = 5,
+    X6 = 6,
+    X7 = 7,
+    X8 = 8,
+    X9 = 9,
+    X10 = 10,
+    X11 = 11,
+    X12 = 12,
+    X13 = 13,
+    X14 = 14,
+    X15 = 15,
+    X16 = 16,
+    X17 = 17,
+    X18 = 18,
+    X19 = 19,
+    X20 = 20,
+    X21 = 21,
+    X22 = 22,
+    X23 = 23,
+    X24 = 24,
+    X25 = 25,
+    X26 = 26,
+    X27 = 27,
+    X28 = 28,
+    /// The frame pointer (x29).
+    Fp = 29,
+    /// The link register (x30).
+    Lr = 30,
+    Sp = 31,
+    Pc = 32,
+}
+
+static AARCH64_REG_NAMES: [&str; 33] = [
+    "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13", "x14",
+    "x15", "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27",
+    "x28", "x29", "lr", "sp", "pc",
+];
+
+impl Register for Aarch64Reg {
+    fn index(self) -> u8 {
+        self as u8
+    }
+
+    fn name(self) -> &'static str {
+        AARCH64_REG_NAMES[self as usize]
+    }
+}
+
+/// The registers of the architecture this crate is compiled for.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub type Reg = X86Reg;
+
+/// The registers of the architecture this crate is compiled for.
+#[cfg(target_arch = "aarch64")]
+pub type Reg = Aarch64Reg;
+
+/// A register set, i.e., how to interpret the bits of a register mask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegArch {
+    /// x86_64 in 64-bit mode.
+    X86_64,
+    /// i386, or a 32-bit task on x86_64.
+    X86,
+    Aarch64,
+}
+
+impl RegArch {
+    /// The register set of the architecture this crate is compiled for.
+    pub fn native() -> Option<RegArch> {
+        if cfg!(target_arch = "x86_64") {
+            Some(RegArch::X86_64)
+        } else if cfg!(target_arch = "x86") {
+            Some(RegArch::X86)
+        } else if cfg!(target_arch = "aarch64") {
+            Some(RegArch::Aarch64)
+        } else {
+            None
+        }
+    }
+
+    /// The register set of a machine name as reported by `uname -m`
+    /// (and stored in perf.data files, see `PerfFile::get_arch`).
+    pub fn from_machine(machine: &str) -> Option<RegArch> {
+        match machine {
+            "x86_64" | "amd64" => Some(RegArch::X86_64),
+            "i386" | "i486" | "i586" | "i686" | "x86" => Some(RegArch::X86),
+            "aarch64" | "arm64" => Some(RegArch::Aarch64),
+            _ => None,
+        }
+    }
+
+    /// The register set of a sample taken on this architecture with the given `abi`
+    /// (the `abi` field of the sample).
+    ///
+    /// Returns `None` if the sample has no registers (`PERF_SAMPLE_REGS_ABI_NONE`, e.g.,
+    /// a kernel thread) or the registers of 32-bit Arm tasks.
+    pub fn for_abi(self, abi: u64) -> Option<RegArch> {
+        let abi = abi as u32;
+        match (self, abi) {
+            (RegArch::X86_64, perf_event::PERF_SAMPLE_REGS_ABI_64) => Some(RegArch::X86_64),
+            (RegArch::X86_64, perf_event::PERF_SAMPLE_REGS_ABI_32) => Some(RegArch::X86),
+            (RegArch::X86, perf_event::PERF_SAMPLE_REGS_ABI_32) => Some(RegArch::X86),
+            (RegArch::Aarch64, perf_event::PERF_SAMPLE_REGS_ABI_64) => Some(RegArch::Aarch64),
+            _ => None,
+        }
+    }
+
+    /// The name of the register at bit `index`.
+    pub fn register_name(self, index: u8) -> Option<&'static str> {
+        let names: &[&'static str] = match self {
+            RegArch::X86_64 => &X86_REG_NAMES,
+            RegArch::X86 => &X86_REG_NAMES[..16],
+            RegArch::Aarch64 => &AARCH64_REG_NAMES,
+        };
+        names.get(index as usize).cloned()
+    }
+
+    /// The mask that selects all registers of this set.
+    pub fn all_registers(self) -> u64 {
+        match self {
+            RegArch::X86_64 => (1 << 24) - 1,
+            RegArch::X86 => (1 << 16) - 1,
+            RegArch::Aarch64 => (1 << 33) - 1,
+        }
+    }
+
+    /// Names the `values` of a sample taken with the register `mask` of the counter.
+    ///
+    /// Values of bits this register set has no name for are left out.
+    pub fn decode(self, mask: u64, values: &[u64]) -> BTreeMap<&'static str, u64> {
+        (0..64u8)
+            .filter(|bit| mask & (1 << bit) != 0)
+            .zip(values.iter())
+            .filter_map(|(bit, value)| self.register_name(bit).map(|name| (name, *value)))
+            .collect()
+    }
+}
@@ -0,0 +1,295 @@
+//! Resolves sampled addresses to the function symbols of the mapped ELF files.
+//!
+//! The `Symbolizer` follows the `PERF_RECORD_MMAP` / `PERF_RECORD_MMAP2` records of a
+//! recording to know which file is mapped where in every process, and looks up
+//! addresses in the `.symtab` (or, for stripped files, `.dynsym`) of that file.
+//! Symbol tables are read once per file.
+//!
+//! # Example
+//! ```no_run
+//! use perfcnt::linux::perf_file::PerfFile;
+//! use perfcnt::linux::perf_format::EventData;
+//! use perfcnt::linux::symbolize::Symbolizer;
+//!
+//! let pf = PerfFile::new(std::fs::read("perf.data").expect("Can not read perf.data"));
+//! let mut symbolizer = Symbolizer::new();
+//! for event in pf.data() {
+//!     symbolizer.process_event(&event.data);
+//!     if let EventData::Sample(ref sample) = event.data {
+//!         for frame in symbolizer.symbolize_sample(sample) {
+//!             println!("{}", frame);
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+
+use object::{Object, ObjectSegment, ObjectSymbol, SymbolKind};
+
+use super::buildid::BuildIdResolver;
+use super::maps::{Mapping, Maps};
+use super::perf_event;
+use super::perf_format::{BuildIdRecord, EventData, SampleRecord};
+
+/// Mappings of the kernel and its modules are reported for this pid.
+const KERNEL_PID: i32 = -1;
+
+/// A function symbol of an ELF file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    /// The demangled name.
+    pub name: String,
+    /// Address of the symbol in the ELF file.
+    pub address: u64,
+    /// Size in bytes, 0 if unknown.
+    pub size: u64,
+}
+
+/// The function symbols of an ELF file.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    /// Sorted by address.
+    symbols: Vec<Symbol>,
+    /// `(file offset, file size, address)` of the loadable segments.
+    segments: Vec<(u64, u64, u64)>,
+}
+
+impl SymbolTable {
+    /// Reads the symbols of the ELF file `data`.
+    pub fn parse(data: &[u8]) -> Option<SymbolTable> {
+        let file = object::File::parse(data).ok()?;
+        let mut symbols: Vec<Symbol> = file
+            .symbols()
+            .chain(file.dynamic_symbols())
+            .filter(|s| s.kind() == SymbolKind::Text && s.is_definition() && s.address() != 0)
+            .filter_map(|s| {
+                Some(Symbol {
+                    name: format!("{:#}", rustc_demangle::demangle(s.name().ok()?)),
+                    address: s.address(),
+                    size: s.size(),
+                })
+            })
+            .collect();
+        // Keep one symbol per address (.symtab comes first and contains .dynsym):
+        symbols.sort_by_key(|s| s.address);
+        symbols.dedup_by_key(|s| s.address);
+
+        let segments = file
+            .segments()
+            .map(|s| {
+                let (offset, size) = s.file_range();
+                (offset, size, s.address())
+            })
+            .collect();
+        Some(SymbolTable { symbols, segments })
+    }
+
+    /// The address of `file_offset` as the ELF file specifies it (before relocation).
+    pub fn file_offset_to_address(&self, file_offset: u64) -> Option<u64> {
+        self.segments
+            .iter()
+            .find(|(offset, size, _)| *offset <= file_offset && file_offset < offset + size)
+            .map(|(offset, _, address)| file_offset - offset + address)
+    }
+
+    /// The symbol that contains `address` (an address in the ELF file).
+    ///
+    /// Symbols without a size extend up to the next symbol.
+    pub fn find(&self, address: u64) -> Option<&Symbol> {
+        let idx = self.symbols.partition_point(|s| s.address <= address);
+        let symbol = &self.symbols[idx.checked_sub(1)?];
+        if symbol.size == 0 || address < symbol.address + symbol.size {
+            Some(symbol)
+        } else {
+            None
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+}
+
+/// Where a sampled address points to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedAddress {
+    /// The sampled address.
+    pub address: u64,
+    /// The mapped file (or pseudo file like `[kernel.kallsyms]`).
+    pub binary: String,
+    /// The address in `binary`, as `addr2line -e <binary>` expects it (the file
+    /// offset if `binary` could not be read).
+    pub offset: u64,
+    /// The function that contains the address.
+    pub symbol: Option<String>,
+    /// Offset of the address from the start of `symbol`.
+    pub symbol_offset: u64,
+}
+
+impl fmt::Display for ResolvedAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.symbol {
+            Some(ref symbol) => write!(f, "{}+{:#x} ({})", symbol, self.symbol_offset, self.binary),
+            None => write!(f, "{}+{:#x}", self.binary, self.offset),
+        }
+    }
+}
+
+/// Resolves addresses of processes to symbols.
+#[derive(Debug, Default)]
+pub struct Symbolizer {
+    processes: HashMap<i32, Maps>,
+    tables: HashMap<String, Option<SymbolTable>>,
+}
+
+impl Symbolizer {
+    pub fn new() -> Symbolizer {
+        Default::default()
+    }
+
+    /// Use `data` as the contents of `filename` instead of reading the file (e.g., for
+    /// files of a recording from another machine).
+    pub fn add_object(&mut self, filename: &str, data: &[u8]) {
+        self.tables
+            .insert(String::from(filename), SymbolTable::parse(data));
+    }
+
+    /// Symbolize the files of `records` (see `PerfFile::get_build_ids`) with the
+    /// binaries that have the recorded build ids. A file without a matching binary
+    /// gets no symbols, rather than the symbols of another version of it.
+    ///
+    /// Returns the records no binary was found for.
+    pub fn add_build_ids<'a>(
+        &mut self,
+        records: &'a [BuildIdRecord],
+        resolver: &BuildIdResolver,
+    ) -> Vec<&'a BuildIdRecord> {
+        let mut missing = Vec::new();
+        for record in records.iter().filter(|r| r.filename.starts_with('/')) {
+            match resolver.locate_with_data(&record.build_id, &record.filename) {
+                Some((_, data)) => self.add_object(&record.filename, &data),
+                None => {
+                    self.tables.insert(record.filename.clone(), None);
+                    missing.push(record);
+                }
+            }
+        }
+        missing
+    }
+
+    /// `mapping` was mapped into process `pid` (-1 for the kernel).
+    pub fn add_mapping(&mut self, pid: i32, mapping: Mapping) {
+        self.processes.entry(pid).or_default().insert(mapping);
+    }
+
+    /// Records the mappings of MMAP and MMAP2 events, other events are ignored.
+    pub fn process_event(&mut self, event: &EventData) {
+        match *event {
+            EventData::MMAP(ref mmap) => self.add_mapping(mmap.pid, mmap.into()),
+            EventData::MMAP2(ref mmap) => self.add_mapping(mmap.ptid.pid, mmap.into()),
+            _ => {}
+        }
+    }
+
+    /// The mappings known for process `pid`.
+    pub fn maps(&self, pid: i32) -> Option<&Maps> {
+        self.processes.get(&pid)
+    }
+
+    /// Resolves `address` in process `pid`, kernel addresses are looked up in the
+    /// mappings of the kernel.
+    ///
+    /// Returns `None` if no mapping contains `address`.
+    pub fn resolve(&mut self, pid: i32, address: u64) -> Option<ResolvedAddress> {
+        let processes = &self.processes;
+        let mapping = processes
+            .get(&pid)
+            .and_then(|maps| maps.find(address))
+            .or_else(|| {
+                processes
+                    .get(&KERNEL_PID)
+                    .and_then(|maps| maps.find(address))
+            })?;
+
+        Some(Symbolizer::resolve_in(&mut self.tables, mapping, address))
+    }
+
+    /// Resolves `address` in `mapping` (e.g., the mapping a `ProcessTracker` found
+    /// for the time of a sample).
+    pub fn resolve_mapping(&mut self, mapping: &Mapping, address: u64) -> ResolvedAddress {
+        Symbolizer::resolve_in(&mut self.tables, mapping, address)
+    }
+
+    fn resolve_in(
+        tables: &mut HashMap<String, Option<SymbolTable>>,
+        mapping: &Mapping,
+        address: u64,
+    ) -> ResolvedAddress {
+        let file_offset = mapping.file_offset(address);
+        let mut resolved = ResolvedAddress {
+            address,
+            binary: mapping.filename.clone(),
+            offset: file_offset,
+            symbol: None,
+            symbol_offset: 0,
+        };
+        if !mapping.is_file() {
+            return resolved;
+        }
+
+        let table = tables.entry(mapping.filename.clone()).or_insert_with(|| {
+            fs::read(&mapping.filename)
+                .ok()
+                .and_then(|data| SymbolTable::parse(&data))
+        });
+        if let Some(table) = table {
+            if let Some(offset) = table.file_offset_to_address(file_offset) {
+                resolved.offset = offset;
+                if let Some(symbol) = table.find(offset) {
+                    resolved.symbol = Some(symbol.name.clone());
+                    resolved.symbol_offset = offset - symbol.address;
+                }
+            }
+        }
+        resolved
+    }
+
+    /// Resolves all addresses of `callchain` in process `pid`, the context markers
+    /// (`PERF_CONTEXT_KERNEL`, `PERF_CONTEXT_USER`, etc.) are skipped.
+    ///
+    /// Addresses without a mapping are kept (with an empty `binary`), so the
+    /// result has one entry per frame.
+    pub fn symbolize_callchain(&mut self, pid: i32, callchain: &[u64]) -> Vec<ResolvedAddress> {
+        callchain
+            .iter()
+            .filter(|ip| **ip < perf_event::PERF_CONTEXT_MAX)
+            .map(|ip| {
+                self.resolve(pid, *ip).unwrap_or(ResolvedAddress {
+                    address: *ip,
+                    binary: String::new(),
+                    offset: *ip,
+                    symbol: None,
+                    symbol_offset: 0,
+                })
+            })
+            .collect()
+    }
+
+    /// Resolves the callchain of `sample` (PERF_SAMPLE_CALLCHAIN), or its ip if the
+    /// sample has no callchain.
+    pub fn symbolize_sample(&mut self, sample: &SampleRecord) -> Vec<ResolvedAddress> {
+        let pid = sample.ptid.as_ref().map_or(KERNEL_PID, |ptid| ptid.pid);
+        match (sample.ips.as_ref(), sample.ip) {
+            (Some(ips), _) => self.symbolize_callchain(pid, ips),
+            (None, Some(ip)) => self.symbolize_callchain(pid, &[ip]),
+            (None, None) => Vec::new(),
+        }
+    }
+}
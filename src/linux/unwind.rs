@@ -0,0 +1,593 @@
+//! Unwinds sampled user stacks with the call frame information of the mapped ELF files.
+//!
+//! Sample with `PERF_SAMPLE_REGS_USER` (all registers of `RegArch::all_registers`, or at
+//! least the instruction pointer, stack pointer and frame pointer) and
+//! `PERF_SAMPLE_STACK_USER`. The `Unwinder` then looks up the `.eh_frame` or
+//! `.debug_frame` rows of every return address in the files of a `Maps`, and follows
+//! them through the copy of the stack. This works without frame pointers, e.g., for
+//! Rust release builds.
+//!
+//! Only x86_64 and aarch64 tasks can be unwound, for other samples the callchain
+//! ends after the sampled instruction pointer.
+//!
+//! # Example
+//! ```no_run
+//! use perfcnt::linux::maps::Maps;
+//! use perfcnt::linux::perf_file::PerfFile;
+//! use perfcnt::linux::perf_format::EventData;
+//! use perfcnt::linux::unwind::Unwinder;
+//!
+//! let pf = PerfFile::new(std::fs::read("perf.data").expect("Can not read perf.data"));
+//! let arch = pf.get_reg_arch().expect("Unknown architecture");
+//! let mut maps = Maps::new();
+//! let mut unwinder = Unwinder::new();
+//! for event in pf.data() {
+//!     match event.data {
+//!         EventData::MMAP2(ref mmap) => maps.insert(mmap.into()),
+//!         EventData::Sample(ref sample) => {
+//!             let callchain = unwinder.unwind_sample(sample, &pf.attrs[0], arch, &maps);
+//!             println!("{:x?}", callchain);
+//!         }
+//!         _ => {}
+//!     }
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs;
+use std::ops::Range;
+
+use gimli::{
+    BaseAddresses, CfaRule, CieOrFde, DebugFrame, EhFrame, EndianSlice, Register, RegisterRule,
+    RunTimeEndian, UnwindContext, UnwindSection, UnwindTableRow,
+};
+use object::{CompressionFormat, Object, ObjectSection, ObjectSegment};
+
+use super::buildid::BuildIdResolver;
+use super::maps::Maps;
+use super::perf_format::{BuildIdRecord, EventAttr, SampleRecord};
+use super::regs::RegArch;
+
+type Slice<'a> = EndianSlice<'a, RunTimeEndian>;
+
+/// Callchains are cut off after this many frames.
+const DEFAULT_MAX_FRAMES: usize = 256;
+
+/// Number of DWARF registers we track (x86_64 uses 0 - 16, aarch64 0 - 31).
+const MAX_DWARF_REGS: usize = 32;
+
+/// How the registers of a sample map to DWARF register numbers.
+struct DwarfRegs {
+    /// Sampled register name and its DWARF number.
+    names: &'static [(&'static str, u16)],
+    /// Name of the sampled instruction pointer.
+    pc: &'static str,
+    sp: u16,
+    /// The column that holds the return address.
+    ra: u16,
+    /// Does the return address live in a register before it is spilled (`lr`)?
+    link_register: bool,
+    /// Registers the ABI preserves across calls, they keep their value if the CFI
+    /// has no rule for them.
+    callee_saved: &'static [u16],
+}
+
+static X86_64_DWARF_REGS: DwarfRegs = DwarfRegs {
+    names: &[
+        ("AX", 0),
+        ("DX", 1),
+        ("CX", 2),
+        ("BX", 3),
+        ("SI", 4),
+        ("DI", 5),
+        ("BP", 6),
+        ("SP", 7),
+        ("R8", 8),
+        ("R9", 9),
+        ("R10", 10),
+        ("R11", 11),
+        ("R12", 12),
+        ("R13", 13),
+        ("R14", 14),
+        ("R15", 15),
+    ],
+    pc: "IP",
+    sp: 7,
+    ra: 16,
+    link_register: false,
+    callee_saved: &[3, 6, 12, 13, 14, 15],
+};
+
+static AARCH64_DWARF_REGS: DwarfRegs = DwarfRegs {
+    names: &[
+        ("x0", 0),
+        ("x1", 1),
+        ("x2", 2),
+        ("x3", 3),
+        ("x4", 4),
+        ("x5", 5),
+        ("x6", 6),
+        ("x7", 7),
+        ("x8", 8),
+        ("x9", 9),
+        ("x10", 10),
+        ("x11", 11),
+        ("x12", 12),
+        ("x13", 13),
+        ("x14", 14),
+        ("x15", 15),
+        ("x16", 16),
+        ("x17", 17),
+        ("x18", 18),
+        ("x19", 19),
+        ("x20", 20),
+        ("x21", 21),
+        ("x22", 22),
+        ("x23", 23),
+        ("x24", 24),
+        ("x25", 25),
+        ("x26", 26),
+        ("x27", 27),
+        ("x28", 28),
+        ("x29", 29),
+        ("lr", 30),
+        ("sp", 31),
+    ],
+    pc: "pc",
+    sp: 31,
+    ra: 30,
+    link_register: true,
+    callee_saved: &[19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29],
+};
+
+/// The copy of the user stack in a sample, starting at the sampled stack pointer.
+struct Stack<'a> {
+    base: u64,
+    data: &'a [u8],
+}
+
+impl<'a> Stack<'a> {
+    fn read(&self, addr: u64) -> Option<u64> {
+        let offset = addr.checked_sub(self.base)? as usize;
+        let bytes = self.data.get(offset..offset.checked_add(8)?)?;
+        Some(u64::from_le_bytes(bytes.try_into().ok()?))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameSection {
+    EhFrame,
+    DebugFrame,
+}
+
+/// Address range of a frame description entry and where to find it.
+#[derive(Debug)]
+struct FdeEntry {
+    start: u64,
+    end: u64,
+    section: FrameSection,
+    offset: usize,
+}
+
+/// The call frame information of an ELF file.
+struct ObjectFrames {
+    data: Vec<u8>,
+    endian: RunTimeEndian,
+    address_size: u8,
+    aarch64: bool,
+    bases: BaseAddresses,
+    eh_frame: Option<Range<usize>>,
+    debug_frame: Option<Range<usize>>,
+    /// `(file offset, file size, address)` of the loadable segments.
+    segments: Vec<(u64, u64, u64)>,
+    /// Sorted by start address.
+    fdes: Vec<FdeEntry>,
+}
+
+impl ObjectFrames {
+    fn load(path: &str) -> Option<ObjectFrames> {
+        let data = fs::read(path).ok()?;
+        ObjectFrames::parse(data)
+    }
+
+    fn parse(data: Vec<u8>) -> Option<ObjectFrames> {
+        let (endian, address_size, aarch64, bases, eh_frame, debug_frame, segments) = {
+            let file = object::File::parse(&*data).ok()?;
+            let endian = if file.is_little_endian() {
+                RunTimeEndian::Little
+            } else {
+                RunTimeEndian::Big
+            };
+            let address_size = if file.is_64() { 8 } else { 4 };
+            let aarch64 = file.architecture() == object::Architecture::Aarch64;
+
+            let section_range = |name: &str| {
+                let section = file.section_by_name(name)?;
+                let range = section.compressed_file_range().ok()?;
+                if range.format != CompressionFormat::None {
+                    return None;
+                }
+                // Empty in separate debug info files (SHT_NOBITS):
+                if range.uncompressed_size == 0 {
+                    return None;
+                }
+                let start = range.offset as usize;
+                let end = start.checked_add(range.uncompressed_size as usize)?;
+                Some((start..end, section.address()))
+            };
+
+            let mut bases = BaseAddresses::default();
+            if let Some(text) = file.section_by_name(".text") {
+                bases = bases.set_text(text.address());
+            }
+            if let Some(got) = file.section_by_name(".got") {
+                bases = bases.set_got(got.address());
+            }
+            let eh_frame = section_range(".eh_frame").map(|(range, address)| {
+                bases = bases.clone().set_eh_frame(address);
+                range
+            });
+            let debug_frame = section_range(".debug_frame").map(|(range, _)| range);
+
+            let segments = file
+                .segments()
+                .map(|s| {
+                    let (offset, size) = s.file_range();
+                    (offset, size, s.address())
+                })
+                .collect::<Vec<_>>();
+            (
+                endian,
+                address_size,
+                aarch64,
+                bases,
+                eh_frame,
+                debug_frame,
+                segments,
+            )
+        };
+        if eh_frame.is_none() && debug_frame.is_none() {
+            return None;
+        }
+
+        let mut frames = ObjectFrames {
+            data,
+            endian,
+            address_size,
+            aarch64,
+            bases,
+            eh_frame,
+            debug_frame,
+            segments,
+            fdes: Vec::new(),
+        };
+        frames.fdes = frames.index_fdes();
+        Some(frames)
+    }
+
+    fn eh_frame_section(&self) -> Option<EhFrame<Slice<'_>>> {
+        let range = self.eh_frame.clone()?;
+        let mut section = EhFrame::new(self.data.get(range)?, self.endian);
+        section.set_address_size(self.address_size);
+        if self.aarch64 {
+            section.set_vendor(gimli::Vendor::AArch64);
+        }
+        Some(section)
+    }
+
+    fn debug_frame_section(&self) -> Option<DebugFrame<Slice<'_>>> {
+        let range = self.debug_frame.clone()?;
+        let mut section = DebugFrame::new(self.data.get(range)?, self.endian);
+        section.set_address_size(self.address_size);
+        if self.aarch64 {
+            section.set_vendor(gimli::Vendor::AArch64);
+        }
+        Some(section)
+    }
+
+    /// Reads the address ranges of all FDEs, `.eh_frame` takes precedence.
+    fn index_fdes(&self) -> Vec<FdeEntry> {
+        let mut fdes = Vec::new();
+        if let Some(section) = self.eh_frame_section() {
+            index_section(&section, &self.bases, FrameSection::EhFrame, &mut fdes);
+        }
+        if let Some(section) = self.debug_frame_section() {
+            index_section(&section, &self.bases, FrameSection::DebugFrame, &mut fdes);
+        }
+        fdes.sort_by_key(|f| (f.start, f.section != FrameSection::EhFrame));
+        fdes
+    }
+
+    /// The address of `file_offset` as the ELF file specifies it (before relocation).
+    fn file_offset_to_address(&self, file_offset: u64) -> Option<u64> {
+        self.segments
+            .iter()
+            .find(|(offset, size, _)| *offset <= file_offset && file_offset < offset + size)
+            .map(|(offset, _, address)| file_offset - offset + address)
+    }
+
+    fn find_row<'ctx>(
+        &self,
+        ctx: &'ctx mut UnwindContext<usize>,
+        address: u64,
+    ) -> Option<&'ctx UnwindTableRow<usize>> {
+        let idx = self.fdes.partition_point(|f| f.start <= address);
+        let fde = self.fdes[..idx].iter().rev().find(|f| address < f.end)?;
+        match fde.section {
+            FrameSection::EhFrame => {
+                let section = self.eh_frame_section()?;
+                unwind_row(&section, &self.bases, ctx, fde.offset, address)
+            }
+            FrameSection::DebugFrame => {
+                let section = self.debug_frame_section()?;
+                unwind_row(&section, &self.bases, ctx, fde.offset, address)
+            }
+        }
+    }
+}
+
+fn index_section<'a, S: UnwindSection<Slice<'a>>>(
+    section: &S,
+    bases: &BaseAddresses,
+    kind: FrameSection,
+    fdes: &mut Vec<FdeEntry>,
+) {
+    let mut entries = section.entries(bases);
+    while let Ok(Some(entry)) = entries.next() {
+        if let CieOrFde::Fde(partial) = entry {
+            if let Ok(fde) = partial.parse(S::cie_from_offset) {
+                fdes.push(FdeEntry {
+                    start: fde.initial_address(),
+                    end: fde.end_address(),
+                    section: kind,
+                    offset: fde.offset(),
+                });
+            }
+        }
+    }
+}
+
+fn unwind_row<'a, 'ctx, S: UnwindSection<Slice<'a>>>(
+    section: &S,
+    bases: &BaseAddresses,
+    ctx: &'ctx mut UnwindContext<usize>,
+    offset: usize,
+    address: u64,
+) -> Option<&'ctx UnwindTableRow<usize>> {
+    let fde = section
+        .fde_from_offset(bases, S::Offset::from(offset), S::cie_from_offset)
+        .ok()?;
+    fde.unwind_info_for_address(section, bases, ctx, address)
+        .ok()
+}
+
+/// Turns sampled user stacks into callchains.
+///
+/// The call frame information of every file is read once and kept for later samples.
+pub struct Unwinder {
+    objects: HashMap<String, Option<ObjectFrames>>,
+    ctx: UnwindContext<usize>,
+    max_frames: usize,
+}
+
+impl Default for Unwinder {
+    fn default() -> Unwinder {
+        Unwinder::new()
+    }
+}
+
+impl Unwinder {
+    pub fn new() -> Unwinder {
+        Unwinder {
+            objects: HashMap::new(),
+            ctx: UnwindContext::new(),
+            max_frames: DEFAULT_MAX_FRAMES,
+        }
+    }
+
+    /// Cut off callchains after `max_frames` frames (default 256).
+    pub fn set_max_frames(&mut self, max_frames: usize) {
+        self.max_frames = max_frames;
+    }
+
+    /// Use `data` as the contents of `filename` instead of reading the file (e.g., for
+    /// files of a recording from another machine).
+    pub fn add_object(&mut self, filename: &str, data: Vec<u8>) {
+        self.objects
+            .insert(String::from(filename), ObjectFrames::parse(data));
+    }
+
+    /// Unwind through the binaries with the build ids of `records` (see
+    /// `PerfFile::get_build_ids`) rather than the files at the recorded paths. The
+    /// unwinding stops at a file without a matching binary.
+    ///
+    /// Returns the records no binary was found for.
+    pub fn add_build_ids<'a>(
+        &mut self,
+        records: &'a [BuildIdRecord],
+        resolver: &BuildIdResolver,
+    ) -> Vec<&'a BuildIdRecord> {
+        let mut missing = Vec::new();
+        for record in records.iter().filter(|r| r.filename.starts_with('/')) {
+            // Separate debug info files have the CFI in .debug_frame at best:
+            let frames = resolver
+                .locate_all(&record.build_id, &record.filename)
+                .find_map(|(_, data)| ObjectFrames::parse(data));
+            match frames {
+                Some(frames) => {
+                    self.objects.insert(record.filename.clone(), Some(frames));
+                }
+                None => {
+                    self.objects.insert(record.filename.clone(), None);
+                    missing.push(record);
+                }
+            }
+        }
+        missing
+    }
+
+    /// Unwinds a sample of a perf.data file, `attr` is the event the sample belongs to
+    /// and `arch` the architecture it was recorded on (see `PerfFile::get_reg_arch`).
+    ///
+    /// Returns the user mode callchain, starting with the sampled instruction pointer,
+    /// or an empty callchain if the sample has no user mode registers.
+    pub fn unwind_sample(
+        &mut self,
+        sample: &SampleRecord,
+        attr: &EventAttr,
+        arch: RegArch,
+        maps: &Maps,
+    ) -> Vec<u64> {
+        let arch = match sample.abi_user.and_then(|abi| arch.for_abi(abi)) {
+            Some(arch) => arch,
+            None => return Vec::new(),
+        };
+        let regs = match sample.regs_user {
+            Some(ref values) => arch.decode(attr.sample_regs_user, values),
+            None => return Vec::new(),
+        };
+        let stack = sample.user_stack.as_deref().unwrap_or(&[]);
+        let len = sample.dyn_size.map_or(stack.len(), |size| size as usize);
+        self.unwind(arch, &regs, &stack[..len.min(stack.len())], maps)
+    }
+
+    /// Unwinds a sample of a `SamplingPerfCounter` of this process or a process
+    /// that runs on the same architecture.
+    pub fn unwind_live_sample(&mut self, sample: &super::SampleRecord, maps: &Maps) -> Vec<u64> {
+        match (sample.user_regs(), sample.user_arch()) {
+            (Some(regs), Some(arch)) => self.unwind(arch, &regs, sample.user_stack(), maps),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Unwinds from the registers `regs` (named like `RegArch::decode` does) with
+    /// `stack`, the memory above the stack pointer in `regs`.
+    pub fn unwind(
+        &mut self,
+        arch: RegArch,
+        regs: &BTreeMap<&'static str, u64>,
+        stack: &[u8],
+        maps: &Maps,
+    ) -> Vec<u64> {
+        let mut pc = match regs.get(arch_pc_name(arch)) {
+            Some(pc) => *pc,
+            None => return Vec::new(),
+        };
+        let mut callchain = vec![pc];
+        let dwarf = match arch {
+            RegArch::X86_64 => &X86_64_DWARF_REGS,
+            RegArch::Aarch64 => &AARCH64_DWARF_REGS,
+            RegArch::X86 => return callchain,
+        };
+
+        let mut registers = [None; MAX_DWARF_REGS];
+        for (name, reg) in dwarf.names {
+            registers[*reg as usize] = regs.get(name).cloned();
+        }
+        let stack = match registers[dwarf.sp as usize] {
+            Some(sp) => Stack {
+                base: sp,
+                data: stack,
+            },
+            None => return callchain,
+        };
+
+        while callchain.len() < self.max_frames {
+            let first = callchain.len() == 1;
+            let mapping = match maps.find(pc) {
+                Some(mapping) if mapping.is_file() => mapping,
+                _ => break,
+            };
+            let object = self
+                .objects
+                .entry(mapping.filename.clone())
+                .or_insert_with(|| ObjectFrames::load(&mapping.filename));
+            let object = match object {
+                Some(object) => object,
+                None => break,
+            };
+            // A return address points after the call, which may be outside the caller:
+            let lookup = if first { pc } else { pc - 1 };
+            let address = match object.file_offset_to_address(mapping.file_offset(lookup)) {
+                Some(address) => address,
+                None => break,
+            };
+            let row = match object.find_row(&mut self.ctx, address) {
+                Some(row) => row,
+                None => break,
+            };
+            let (caller, ra) = match step(dwarf, row, &registers, &stack, first) {
+                Some(step) => step,
+                None => break,
+            };
+
+            let sp = registers[dwarf.sp as usize];
+            if ra == 0 || (ra == pc && caller[dwarf.sp as usize] == sp) {
+                break;
+            }
+            callchain.push(ra);
+            pc = ra;
+            registers = caller;
+        }
+        callchain
+    }
+}
+
+fn arch_pc_name(arch: RegArch) -> &'static str {
+    match arch {
+        RegArch::X86_64 | RegArch::X86 => X86_64_DWARF_REGS.pc,
+        RegArch::Aarch64 => AARCH64_DWARF_REGS.pc,
+    }
+}
+
+/// Computes the registers of the caller and the return address from `row`.
+fn step(
+    dwarf: &DwarfRegs,
+    row: &UnwindTableRow<usize>,
+    registers: &[Option<u64>; MAX_DWARF_REGS],
+    stack: &Stack,
+    first: bool,
+) -> Option<([Option<u64>; MAX_DWARF_REGS], u64)> {
+    let cfa = match *row.cfa() {
+        CfaRule::RegisterAndOffset { register, offset } => registers
+            .get(register.0 as usize)
+            .cloned()??
+            .wrapping_add(offset as u64),
+        CfaRule::Expression(_) => return None,
+    };
+    let recover = |reg: u16| -> Option<u64> {
+        match row.register(Register(reg)) {
+            // gimli reports registers without a rule as undefined, the ABI says
+            // callee-saved ones are untouched:
+            RegisterRule::Undefined if dwarf.callee_saved.contains(&reg) => {
+                registers.get(reg as usize).cloned().flatten()
+            }
+            RegisterRule::Undefined => None,
+            RegisterRule::SameValue => registers.get(reg as usize).cloned().flatten(),
+            RegisterRule::Offset(offset) => stack.read(cfa.wrapping_add(offset as u64)),
+            RegisterRule::ValOffset(offset) => Some(cfa.wrapping_add(offset as u64)),
+            RegisterRule::Register(other) => registers.get(other.0 as usize).cloned().flatten(),
+            RegisterRule::Constant(value) => Some(value),
+            _ => None,
+        }
+    };
+
+    let ra = match row.register(Register(dwarf.ra)) {
+        // Only a leaf function can still have its return address in the link register,
+        // otherwise an undefined return address marks the outermost frame:
+        RegisterRule::Undefined if first && dwarf.link_register => {
+            registers.get(dwarf.ra as usize).cloned().flatten()?
+        }
+        RegisterRule::Undefined => return None,
+        _ => recover(dwarf.ra)?,
+    };
+
+    let mut caller = [None; MAX_DWARF_REGS];
+    for (reg, value) in caller.iter_mut().enumerate() {
+        *value = recover(reg as u16);
+    }
+    caller[dwarf.sp as usize] = Some(cfa);
+    Some((caller, ra))
+}
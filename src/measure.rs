@@ -0,0 +1,150 @@
+//! Helpers to measure a region of code without repeating the
+//! reset/start/stop/read sequence every time.
+//!
+//! Example usage:
+//!
+//! ```no_run
+//! use perfcnt::{Measure, PerfCounter};
+//! use perfcnt::linux::{PerfCounterBuilderLinux, HardwareEventType};
+//!
+//! let mut counters: Vec<PerfCounter> = vec![
+//!     PerfCounterBuilderLinux::from_hardware_event(HardwareEventType::CPUCycles)
+//!         .finish().expect("Could not create the counter"),
+//!     PerfCounterBuilderLinux::from_hardware_event(HardwareEventType::Instructions)
+//!         .finish().expect("Could not create the counter"),
+//! ];
+//!
+//! let (sum, values) = counters
+//!     .measure(|| (0..1000u64).sum::<u64>())
+//!     .expect("Can not measure");
+//! println!("sum={} cycles={} instructions={}", sum, values[0], values[1]);
+//!
+//! let mut result = None;
+//! {
+//!     let _guard = counters.guard(&mut result).expect("Can not start the counters");
+//!     // ... region to measure ...
+//! }
+//! println!("{:?}", result);
+//! ```
+
+use std::io;
+
+use crate::linux::{GroupReadFormat, PerfCounterGroup};
+use crate::AbstractPerfCounter;
+
+/// Something that can measure a region of code.
+pub trait Measure {
+    /// What a measurement produces.
+    type Output;
+
+    /// Reset the counters and start measuring.
+    fn begin(&mut self) -> Result<(), io::Error>;
+
+    /// Stop measuring and read the counters.
+    fn end(&mut self) -> Result<Self::Output, io::Error>;
+
+    /// Measure the execution of `f`.
+    ///
+    /// Returns the result of `f` along with what was counted while it ran.
+    fn measure<R, F: FnOnce() -> R>(&mut self, f: F) -> Result<(R, Self::Output), io::Error> {
+        self.begin()?;
+        let ret = f();
+        let values = self.end()?;
+        Ok((ret, values))
+    }
+
+    /// Start measuring until the returned guard goes out of scope.
+    ///
+    /// On drop, the measurement is stored in `slot`. Errors that happen while dropping
+    /// are lost, use `MeasureGuard::finish` if you need them.
+    fn guard<'a>(
+        &'a mut self,
+        slot: &'a mut Option<Self::Output>,
+    ) -> Result<MeasureGuard<'a, Self>, io::Error> {
+        self.begin()?;
+        Ok(MeasureGuard {
+            counter: Some(self),
+            slot,
+        })
+    }
+}
+
+/// Measures from its creation until it is dropped (or `finish` is called).
+pub struct MeasureGuard<'a, M: Measure + ?Sized> {
+    counter: Option<&'a mut M>,
+    slot: &'a mut Option<M::Output>,
+}
+
+impl<'a, M: Measure + ?Sized> MeasureGuard<'a, M> {
+    /// Stop measuring now and store the result in the slot.
+    pub fn finish(mut self) -> Result<(), io::Error> {
+        match self.counter.take() {
+            Some(counter) => {
+                *self.slot = Some(counter.end()?);
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a, M: Measure + ?Sized> Drop for MeasureGuard<'a, M> {
+    fn drop(&mut self) {
+        if let Some(counter) = self.counter.take() {
+            *self.slot = counter.end().ok();
+        }
+    }
+}
+
+impl<T: AbstractPerfCounter> Measure for T {
+    type Output = u64;
+
+    fn begin(&mut self) -> Result<(), io::Error> {
+        self.reset()?;
+        self.start()
+    }
+
+    fn end(&mut self) -> Result<u64, io::Error> {
+        self.stop()?;
+        self.read()
+    }
+}
+
+/// A set of counters, they are started and stopped one after the other.
+///
+/// The values are in the same order as the counters.
+impl<T: AbstractPerfCounter> Measure for [T] {
+    type Output = Vec<u64>;
+
+    fn begin(&mut self) -> Result<(), io::Error> {
+        for c in self.iter() {
+            c.reset()?;
+        }
+        for c in self.iter() {
+            c.start()?;
+        }
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<Vec<u64>, io::Error> {
+        for c in self.iter() {
+            c.stop()?;
+        }
+        self.iter_mut().map(|c| c.read()).collect()
+    }
+}
+
+/// A counter group, all events are started and stopped at exactly the same time.
+impl Measure for PerfCounterGroup {
+    type Output = GroupReadFormat;
+
+    fn begin(&mut self) -> Result<(), io::Error> {
+        self.reset()?;
+        self.start()
+    }
+
+    fn end(&mut self) -> Result<GroupReadFormat, io::Error> {
+        self.stop()?;
+        self.read()
+    }
+}
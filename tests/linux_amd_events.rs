@@ -0,0 +1,68 @@
+extern crate perfcnt;
+
+use perfcnt::linux::amd::{find_zen_event, AmdEvent, ZEN_EVENTS};
+use perfcnt::linux::perf_format::{EventAttr, EventAttrType};
+use perfcnt::linux::pmu::FormatField;
+use perfcnt::linux::PerfCounterBuilderLinux;
+
+fn config(name: &str) -> u64 {
+    let event = find_zen_event(name).expect("Event not in table");
+    PerfCounterBuilderLinux::from_amd_event(event)
+        .expect("Can not encode event")
+        .attributes()
+        .config
+}
+
+#[test]
+pub fn test_zen_event_configs() {
+    assert_eq!(config("ex_ret_instr"), 0xc0);
+    assert_eq!(config("ls_l1_d_tlb_miss.all"), 0xff45);
+    assert_eq!(config("l2_request_g1.all_no_prefetch"), 0xf960);
+    assert_eq!(config("ex_ret_fused_instr"), 0x1_0000_00d0);
+    assert_eq!(
+        config("ic_tag_hit_miss.all_instruction_cache_accesses"),
+        0x1_0000_1f8e
+    );
+    assert_eq!(
+        config("op_cache_hit_miss.all_op_cache_accesses"),
+        0x2_0000_078f
+    );
+
+    let pc = PerfCounterBuilderLinux::from_amd_event(&ZEN_EVENTS[0]).unwrap();
+    assert_eq!(pc.attributes().attr_type(), EventAttrType::Raw);
+}
+
+#[test]
+pub fn test_amd_event_fields() {
+    let mut event = AmdEvent::new("test", 0x1c0, 0x01, "Test event");
+    event.counter_mask = 2;
+    event.edge = true;
+    event.invert = true;
+    assert_eq!(event.config().unwrap(), 0x1_02_84_01_c0);
+
+    event.event_select = 0x1000;
+    assert!(event.config().is_err());
+}
+
+#[test]
+pub fn test_matches_cpu_pmu_format() {
+    // The format the kernel advertises for the cpu PMU on AMD:
+    let formats = [
+        ("config:0-7,32-35", 0x28f),
+        ("config:8-15", 0x07),
+        ("config:18", 0),
+        ("config:23", 0),
+        ("config:24-31", 0),
+    ];
+    let mut attr: EventAttr = Default::default();
+    for (format, value) in formats.iter() {
+        FormatField::parse(format)
+            .unwrap()
+            .apply(&mut attr, *value)
+            .unwrap();
+    }
+    assert_eq!(
+        attr.config,
+        config("op_cache_hit_miss.all_op_cache_accesses")
+    );
+}
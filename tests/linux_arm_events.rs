@@ -0,0 +1,83 @@
+extern crate perfcnt;
+
+use perfcnt::linux::arm::{
+    find_pmuv3_event, ArmPmuV3Event, ARM_PMUV3_EVENTS, ARM_PMUV3_EXTENDED_EVENTS,
+};
+use perfcnt::linux::perf_format::{EventAttr, EventAttrType};
+use perfcnt::linux::PerfCounterBuilderLinux;
+
+#[test]
+pub fn test_pmuv3_event_table() {
+    // The table covers the whole common event space, in order:
+    for (number, event) in ARM_PMUV3_EVENTS.iter().enumerate() {
+        assert_eq!(event.number as usize, number);
+        assert_eq!(find_pmuv3_event(event.name), Some(event));
+    }
+    assert!(find_pmuv3_event("cycles").is_none());
+    for pair in ARM_PMUV3_EXTENDED_EVENTS.windows(2) {
+        assert!(pair[0].number < pair[1].number);
+    }
+    for event in ARM_PMUV3_EXTENDED_EVENTS.iter() {
+        assert_eq!(find_pmuv3_event(event.name), Some(event));
+    }
+
+    let event = find_pmuv3_event("CPU_CYCLES").expect("Event not in table");
+    let pc = PerfCounterBuilderLinux::from_arm_event(event);
+    assert_eq!(pc.attributes().attr_type(), EventAttrType::Raw);
+    assert_eq!(pc.attributes().config, 0x11);
+    assert_eq!(
+        PerfCounterBuilderLinux::from_arm_event(find_pmuv3_event("BR_MIS_PRED").unwrap())
+            .attributes()
+            .config,
+        0x10
+    );
+}
+
+#[test]
+pub fn test_pmuv3_decode() {
+    assert_eq!(
+        ArmPmuV3Event::from_config(0x08).unwrap().name,
+        "INST_RETIRED"
+    );
+    assert_eq!(ArmPmuV3Event::from_config(0x3f).unwrap().name, "STALL_SLOT");
+    assert!(ArmPmuV3Event::from_config(0x40).is_none());
+
+    let event = find_pmuv3_event("L1D_CACHE_REFILL").unwrap();
+    let pc = PerfCounterBuilderLinux::from_arm_event(event);
+    assert_eq!(ArmPmuV3Event::from_event_attr(pc.attributes()), Some(event));
+
+    // Same config, but a generic hardware event:
+    let attr = EventAttr {
+        config: 0x03,
+        ..Default::default()
+    };
+    assert!(ArmPmuV3Event::from_event_attr(&attr).is_none());
+
+    // Extended events use all 16 bits of the event number:
+    assert_eq!(
+        ArmPmuV3Event::from_config(0x4004).unwrap().name,
+        "CNT_CYCLES"
+    );
+    assert_eq!(
+        ArmPmuV3Event::from_config(0xffff_0000_4009).unwrap().name,
+        "L2D_CACHE_LMISS_RD"
+    );
+    assert!(ArmPmuV3Event::from_config(0x4008).is_none());
+
+    // An event of the armv8_pmuv3_0 PMU, with its dynamic type:
+    let attr = EventAttr {
+        attr_type: 8,
+        config: 0x4006,
+        ..Default::default()
+    };
+    assert!(ArmPmuV3Event::from_event_attr(&attr).is_none());
+    assert!(ArmPmuV3Event::from_pmu_event_attr(&attr, 9).is_none());
+    assert_eq!(
+        ArmPmuV3Event::from_pmu_event_attr(&attr, 8).unwrap().name,
+        "L1I_CACHE_LMISS"
+    );
+    assert_eq!(
+        ArmPmuV3Event::from_pmu_event_attr(pc.attributes(), 8),
+        Some(event)
+    );
+}
@@ -0,0 +1,92 @@
+extern crate perfcnt;
+
+use perfcnt::linux::parser::parse_sample_record;
+use perfcnt::linux::perf_format::{
+    BranchEntry, BranchSampleType, BranchType, EventAttr, SampleFormatFlags,
+};
+use perfcnt::linux::{HardwareEventType, PerfCounterBuilderLinux};
+
+fn to_bytes(values: &[u64]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .collect()
+}
+
+#[test]
+pub fn test_branch_entry_flags() {
+    // mispred, in_tx, 300 cycles, type call:
+    let entry = BranchEntry {
+        from: 0x1000,
+        to: 0x2000,
+        flags: 1 | 1 << 2 | 300 << 4 | 4 << 20,
+    };
+    assert!(entry.mispred());
+    assert!(!entry.predicted());
+    assert!(entry.in_tx());
+    assert!(!entry.abort());
+    assert_eq!(entry.cycles(), 300);
+    assert_eq!(entry.branch_type(), BranchType::Call);
+
+    // Extended type, new_type = 2 (instruction fault):
+    let entry = BranchEntry {
+        from: 0,
+        to: 0,
+        flags: 1 << 1 | 15 << 20 | 2 << 26,
+    };
+    assert!(entry.predicted());
+    assert_eq!(entry.cycles(), 0);
+    assert_eq!(entry.branch_type(), BranchType::Extended(2));
+}
+
+#[test]
+pub fn test_branch_sample_type_builder() {
+    let mut pc = PerfCounterBuilderLinux::from_hardware_event(HardwareEventType::BranchMisses);
+    pc.enable_sampling_branch_stack();
+    assert!(pc
+        .attributes()
+        .sample_type
+        .contains(SampleFormatFlags::PERF_SAMPLE_BRANCH_STACK));
+    assert_eq!(
+        pc.attributes().branch_sample_type,
+        BranchSampleType::PERF_SAMPLE_BRANCH_ANY
+    );
+
+    let types =
+        BranchSampleType::PERF_SAMPLE_BRANCH_USER | BranchSampleType::PERF_SAMPLE_BRANCH_ANY_CALL;
+    pc.set_branch_sample_type(types);
+    pc.enable_sampling_branch_stack();
+    assert_eq!(pc.attributes().branch_sample_type, types);
+    assert_eq!(
+        types.privilege_levels(),
+        BranchSampleType::PERF_SAMPLE_BRANCH_USER
+    );
+}
+
+#[test]
+pub fn test_parse_branch_stack() {
+    let mut attr = EventAttr {
+        sample_type: SampleFormatFlags::PERF_SAMPLE_IP
+            | SampleFormatFlags::PERF_SAMPLE_BRANCH_STACK,
+        branch_sample_type: BranchSampleType::PERF_SAMPLE_BRANCH_ANY,
+        ..Default::default()
+    };
+
+    let sample = to_bytes(&[0xffff, 2, 0x10, 0x20, 1, 0x30, 0x40, 1 << 1]);
+    let (rest, record) = parse_sample_record(&sample, &attr).expect("Can not parse sample");
+    assert!(rest.is_empty());
+    assert_eq!(record.ip, Some(0xffff));
+    let lbr = record.lbr.expect("No branch stack");
+    assert_eq!(lbr.len(), 2);
+    assert!(lbr[0].mispred());
+    assert_eq!(lbr[1].from, 0x30);
+    assert!(lbr[1].predicted());
+
+    // With the hardware index in front and counters at the end:
+    attr.branch_sample_type |= BranchSampleType::PERF_SAMPLE_BRANCH_HW_INDEX
+        | BranchSampleType::PERF_SAMPLE_BRANCH_COUNTERS;
+    let sample = to_bytes(&[0xffff, 1, 7, 0x10, 0x20, 1, 99]);
+    let (rest, record) = parse_sample_record(&sample, &attr).expect("Can not parse sample");
+    assert!(rest.is_empty());
+    assert_eq!(record.lbr.unwrap()[0].to, 0x20);
+}
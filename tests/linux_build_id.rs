@@ -0,0 +1,175 @@
+extern crate perfcnt;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use perfcnt::linux::buildid::{build_id_hex, read_build_id, BuildIdResolver};
+use perfcnt::linux::parser::parse_build_id_record;
+use perfcnt::linux::perf_format::{
+    BuildIdRecord, EventHeader, EventType, PERF_RECORD_MISC_BUILD_ID_SIZE,
+};
+use perfcnt::linux::symbolize::Symbolizer;
+use perfcnt::linux::unwind::Unwinder;
+
+fn build_id_record(misc: u16, build_id: &[u8], size: u8, filename: &str) -> (EventHeader, Vec<u8>) {
+    let mut body = Vec::new();
+    body.extend_from_slice(&42i32.to_le_bytes());
+    let mut id = [0u8; 24];
+    id[..build_id.len()].copy_from_slice(build_id);
+    id[20] = size;
+    body.extend_from_slice(&id);
+    body.extend_from_slice(filename.as_bytes());
+    // The filename is NUL terminated and padded to 64 bytes:
+    body.resize(4 + 24 + ((filename.len() + 64) & !63), 0);
+    let header = EventHeader {
+        event_type: EventType::BuildId,
+        misc,
+        size: (8 + body.len()) as u16,
+    };
+    (header, body)
+}
+
+#[test]
+fn test_parse_build_id_record() {
+    let sha1: Vec<u8> = (1..=20).collect();
+    let (header, body) = build_id_record(0, &sha1, 0, "/usr/bin/foo");
+    let (rest, record) = parse_build_id_record(&body, &header).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(record.pid, 42);
+    assert_eq!(record.build_id, sha1);
+    assert_eq!(record.filename, "/usr/bin/foo");
+    assert_eq!(
+        record.build_id_hex(),
+        "0102030405060708090a0b0c0d0e0f1011121314"
+    );
+
+    // An MD5 build id with PERF_RECORD_MISC_BUILD_ID_SIZE:
+    let md5: Vec<u8> = (1..=16).collect();
+    let (header, body) = build_id_record(
+        PERF_RECORD_MISC_BUILD_ID_SIZE,
+        &md5,
+        16,
+        "[kernel.kallsyms]",
+    );
+    let (_, record) = parse_build_id_record(&body, &header).unwrap();
+    assert_eq!(record.build_id, md5);
+    assert_eq!(record.filename, "[kernel.kallsyms]");
+}
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("perfcnt-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_build_id_resolver() {
+    let exe = env::current_exe().unwrap();
+    let data = fs::read(&exe).unwrap();
+    let build_id = match read_build_id(&data) {
+        Some(build_id) => build_id,
+        None => return, // Linked without --build-id
+    };
+    let exe = exe.to_str().unwrap();
+    let mut other_id = build_id.clone();
+    other_id[0] ^= 0xff;
+
+    // The binary at the recorded path is only used if the build id matches:
+    let resolver = BuildIdResolver::empty();
+    assert_eq!(resolver.locate(&build_id, exe), Some(PathBuf::from(exe)));
+    assert_eq!(resolver.locate(&other_id, exe), None);
+
+    // A copy in the build id cache is found wherever the file was recorded:
+    let debug_dir = scratch_dir("build-id");
+    let hex = build_id_hex(&build_id);
+    let cached = debug_dir.join(".build-id").join(&hex[..2]).join(&hex[2..]);
+    fs::create_dir_all(&cached).unwrap();
+    fs::write(cached.join("elf"), &data).unwrap();
+
+    let mut resolver = BuildIdResolver::empty();
+    resolver.add_debug_dir(&debug_dir);
+    resolver.set_use_recorded_path(false);
+    assert_eq!(
+        resolver.locate(&build_id, "/opt/app/bin/server"),
+        Some(cached.join("elf"))
+    );
+    assert_eq!(resolver.locate(&build_id, exe), Some(cached.join("elf")));
+    assert_eq!(resolver.locate(&other_id, exe), None);
+
+    fs::remove_dir_all(&debug_dir).unwrap();
+}
+
+/// Turns `data` into something like a separate debug info file: the unwind sections
+/// become SHT_NOBITS (only 64-bit little endian ELF files).
+fn strip_unwind_info(data: &[u8]) -> Vec<u8> {
+    let u16_at = |off: usize| u16::from_le_bytes([data[off], data[off + 1]]) as usize;
+    let u64_at = |off: usize| {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&data[off..off + 8]);
+        u64::from_le_bytes(bytes) as usize
+    };
+    let (shoff, shentsize, shnum, shstrndx) =
+        (u64_at(0x28), u16_at(0x3a), u16_at(0x3c), u16_at(0x3e));
+    let strtab = u64_at(shoff + shstrndx * shentsize + 0x18);
+
+    let mut stripped = data.to_vec();
+    for i in 0..shnum {
+        let header = shoff + i * shentsize;
+        let name = strtab
+            + u32::from_le_bytes([
+                data[header],
+                data[header + 1],
+                data[header + 2],
+                data[header + 3],
+            ]) as usize;
+        let name_end = name + data[name..].iter().position(|b| *b == 0).unwrap();
+        if [&b".eh_frame"[..], b".debug_frame"].contains(&&data[name..name_end]) {
+            stripped[header + 4..header + 8].copy_from_slice(&8u32.to_le_bytes());
+        }
+    }
+    stripped
+}
+
+#[test]
+fn test_debug_file_next_to_binary() {
+    let exe = env::current_exe().unwrap();
+    let data = fs::read(&exe).unwrap();
+    let build_id = match read_build_id(&data) {
+        Some(build_id) if cfg!(all(target_arch = "x86_64", target_os = "linux")) => build_id,
+        _ => return,
+    };
+    let record = BuildIdRecord {
+        pid: 1,
+        build_id: build_id.clone(),
+        filename: String::from("/opt/app/bin/server"),
+    };
+    let records = [record.clone()];
+
+    let debug_dir = scratch_dir("debug-file");
+    let hex = build_id_hex(&build_id);
+    let binary = debug_dir.join(".build-id").join(&hex[..2]).join(&hex[2..]);
+    let debug_file = binary.with_extension("debug");
+    fs::create_dir_all(binary.parent().unwrap()).unwrap();
+    fs::write(&debug_file, strip_unwind_info(&data)).unwrap();
+
+    let mut resolver = BuildIdResolver::empty();
+    resolver.add_debug_dir(&debug_dir);
+
+    // The debug info file has the symbols, but nothing to unwind with:
+    assert_eq!(resolver.locate_record(&record), Some(debug_file.clone()));
+    assert!(Symbolizer::new()
+        .add_build_ids(&records, &resolver)
+        .is_empty());
+    assert_eq!(Unwinder::new().add_build_ids(&records, &resolver).len(), 1);
+
+    // The binary comes first:
+    fs::write(&binary, &data).unwrap();
+    assert_eq!(resolver.locate_record(&record), Some(binary.clone()));
+    assert!(Unwinder::new()
+        .add_build_ids(&records, &resolver)
+        .is_empty());
+
+    fs::remove_dir_all(&debug_dir).unwrap();
+}
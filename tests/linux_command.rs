@@ -0,0 +1,47 @@
+extern crate perfcnt;
+
+use perfcnt::linux::command::measure_command;
+use perfcnt::linux::{PerfCounterBuilderLinux, SoftwareEventType};
+use perfcnt::PerfError;
+use std::io::ErrorKind;
+use std::process::Command;
+
+#[test]
+pub fn test_measure_command() {
+    let events = [
+        PerfCounterBuilderLinux::from_software_event(SoftwareEventType::TaskClock),
+        PerfCounterBuilderLinux::from_software_event(SoftwareEventType::PageFaults),
+    ];
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg("exit 3");
+
+    match measure_command(cmd, &events) {
+        Ok(m) => {
+            assert_eq!(m.status.code(), Some(3));
+            assert_eq!(m.values.len(), 2);
+            assert!(m.values[0].raw > 0);
+        }
+        // The counters include the kernel, the perf_event_open error keeps its
+        // diagnostics as the inner error:
+        Err(e) => assert_eq!(
+            e.get_ref()
+                .and_then(|inner| inner.downcast_ref::<PerfError>())
+                .and_then(|inner| inner.raw_os_error()),
+            Some(13)
+        ),
+    }
+}
+
+#[test]
+pub fn test_measure_missing_command() {
+    let events = [PerfCounterBuilderLinux::from_software_event(
+        SoftwareEventType::TaskClock,
+    )];
+    let cmd = Command::new("/this/command/does/not/exist");
+    let err = measure_command(cmd, &events).expect_err("Ran a missing command");
+    // Unless the counters could not be opened for it in the first place:
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::NotFound | ErrorKind::PermissionDenied
+    ));
+}
@@ -0,0 +1,155 @@
+extern crate perfcnt;
+
+use perfcnt::linux::error::PerfError;
+use perfcnt::linux::parser::parse_event_attr;
+use perfcnt::linux::perf_format::{
+    EventAttr, EventAttrFlags, PERF_ATTR_SIZE_VER0, PERF_ATTR_SIZE_VER5, PERF_ATTR_SIZE_VER7,
+    PERF_ATTR_SIZE_VER8,
+};
+use perfcnt::linux::{PerfCounterBuilderLinux, SoftwareEventType};
+use std::mem;
+use std::slice;
+
+/// The in-memory representation, as a writer of `size` bytes would store it.
+fn to_bytes(attr: &EventAttr, size: u32) -> Vec<u8> {
+    let mut attr = *attr;
+    attr.size = size;
+    let bytes = unsafe {
+        slice::from_raw_parts(
+            &attr as *const EventAttr as *const u8,
+            mem::size_of::<EventAttr>(),
+        )
+    };
+    let mut bytes = bytes.to_vec();
+    bytes.resize(size as usize, 0);
+    bytes
+}
+
+fn sample_attr() -> EventAttr {
+    let mut pc = PerfCounterBuilderLinux::from_software_event(SoftwareEventType::TaskClock);
+    pc.enable_mmap2()
+        .enable_context_switch()
+        .use_clock_id(4)
+        .remove_on_exec()
+        .sigtrap(0xdead)
+        .set_sample_max_stack(32)
+        .set_aux_sample_size(4096)
+        .set_config3(7);
+    *pc.attributes()
+}
+
+#[test]
+pub fn test_event_attr_abi() {
+    assert_eq!(mem::size_of::<EventAttr>(), PERF_ATTR_SIZE_VER8 as usize);
+
+    let attr = sample_attr();
+    assert!(attr.settings.contains(
+        EventAttrFlags::EVENT_ATTR_MMAP2
+            | EventAttrFlags::EVENT_ATTR_CONTEXT_SWITCH
+            | EventAttrFlags::EVENT_ATTR_USE_CLOCKID
+            | EventAttrFlags::EVENT_ATTR_REMOVE_ON_EXEC
+            | EventAttrFlags::EVENT_ATTR_SIGTRAP
+    ));
+    assert_eq!(attr.clock_id, 4);
+    assert_eq!(attr.sig_data, 0xdead);
+
+    let copy = PerfCounterBuilderLinux::from_event_attr(&attr);
+    assert_eq!(copy.attributes().config3, 7);
+}
+
+#[test]
+pub fn test_parse_event_attr_sizes() {
+    let attr = sample_attr();
+
+    // Current size:
+    let bytes = to_bytes(&attr, PERF_ATTR_SIZE_VER8);
+    let (rest, parsed) = parse_event_attr(&bytes).expect("Can not parse attr");
+    assert!(rest.is_empty());
+    assert_eq!(parsed.settings, attr.settings);
+    assert_eq!(parsed.sample_max_stack, 32);
+    assert_eq!(parsed.aux_sample_size, 4096);
+    assert_eq!(parsed.sig_data, 0xdead);
+    assert_eq!(parsed.config3, 7);
+
+    // An older writer does not know about the fields at the end:
+    let bytes = to_bytes(&attr, PERF_ATTR_SIZE_VER5);
+    let (rest, parsed) = parse_event_attr(&bytes).expect("Can not parse attr");
+    assert!(rest.is_empty());
+    assert_eq!(parsed.size, PERF_ATTR_SIZE_VER5);
+    assert_eq!(parsed.clock_id, 4);
+    assert_eq!(parsed.sample_max_stack, 32);
+    assert_eq!(parsed.aux_sample_size, 0);
+    assert_eq!(parsed.config3, 0);
+
+    // A newer one has fields we don't know about:
+    let bytes = to_bytes(&attr, PERF_ATTR_SIZE_VER8 + 8);
+    let (rest, parsed) = parse_event_attr(&bytes).expect("Can not parse attr");
+    assert!(rest.is_empty());
+    assert_eq!(parsed.config3, 7);
+
+    assert!(parse_event_attr(&bytes[..32]).is_err());
+
+    // Attributes follow each other in perf.data, the newer fields of an older writer
+    // are not taken from the next one:
+    let mut bytes = to_bytes(&attr, PERF_ATTR_SIZE_VER5);
+    bytes.extend(to_bytes(&attr, PERF_ATTR_SIZE_VER8));
+    let (rest, parsed) = parse_event_attr(&bytes).expect("Can not parse attr");
+    assert_eq!(rest.len(), PERF_ATTR_SIZE_VER8 as usize);
+    assert_eq!(parsed.aux_sample_size, 0);
+    assert_eq!(parsed.config3, 0);
+    let (rest, parsed) = parse_event_attr(rest).expect("Can not parse attr");
+    assert!(rest.is_empty());
+    assert_eq!(parsed.config3, 7);
+
+    // The kernel does not accept less than the first version either:
+    let mut bytes = to_bytes(&attr, PERF_ATTR_SIZE_VER8);
+    bytes[4..8].copy_from_slice(&32u32.to_le_bytes());
+    assert!(parse_event_attr(&bytes).is_err());
+}
+
+#[test]
+pub fn test_open_with_current_size() {
+    let mut pc = PerfCounterBuilderLinux::from_software_event(SoftwareEventType::TaskClock);
+    pc.set_sample_max_stack(16).use_clock_id(1);
+    match pc.finish() {
+        Ok(counter) => assert!(counter.attributes().size >= PERF_ATTR_SIZE_VER5),
+        // Only if counting in kernel mode is not allowed, never E2BIG:
+        Err(e) => assert_eq!(e.raw_os_error().unwrap(), 13),
+    }
+}
+
+#[test]
+pub fn test_attr_too_new() {
+    let attr = sample_attr();
+    assert!(attr.fields_after(PERF_ATTR_SIZE_VER8).is_empty());
+    assert_eq!(attr.fields_after(PERF_ATTR_SIZE_VER7), vec!["config3"]);
+    assert_eq!(
+        attr.fields_after(PERF_ATTR_SIZE_VER5),
+        vec!["aux_sample_size", "sig_data", "config3"]
+    );
+    assert_eq!(
+        attr.fields_after(PERF_ATTR_SIZE_VER0),
+        vec![
+            "clockid",
+            "sample_max_stack",
+            "aux_sample_size",
+            "sig_data",
+            "config3"
+        ]
+    );
+
+    // A kernel that only knows VER5 writes its size back and fails with E2BIG:
+    let mut written_back = attr;
+    written_back.size = PERF_ATTR_SIZE_VER5;
+    let e = PerfError::from_errno(7, &written_back, false);
+    match e {
+        PerfError::InvalidAttributes { ref attr, errno } => {
+            assert_eq!(errno, 7);
+            assert_eq!(attr.size, PERF_ATTR_SIZE_VER5);
+        }
+        ref e => panic!("Unexpected error {:?}", e),
+    }
+    let message = e.to_string();
+    assert!(message.contains("first 112 bytes"));
+    assert!(message.ends_with("it does not know aux_sample_size, sig_data, config3"));
+}
@@ -0,0 +1,119 @@
+extern crate perfcnt;
+
+use perfcnt::linux::event_str::{format_event, parse_events_with_pmus, ParsedEvent};
+use perfcnt::linux::perf_format::{EventAttrFlags, EventAttrType};
+use perfcnt::linux::pmu::PmuRegistry;
+use perfcnt::linux::{PerfCounterBuilderLinux, PerfCounterGroupBuilder};
+use std::fs;
+use std::path::PathBuf;
+
+fn fake_pmus() -> (PathBuf, PmuRegistry) {
+    let root = std::env::temp_dir().join(format!("perfcnt-event-str-{}", std::process::id()));
+    let cpu = root.join("bus/event_source/devices/cpu");
+    fs::create_dir_all(cpu.join("format")).unwrap();
+    fs::create_dir_all(cpu.join("events")).unwrap();
+    fs::write(cpu.join("type"), "4\n").unwrap();
+    fs::write(cpu.join("format/event"), "config:0-7\n").unwrap();
+    fs::write(cpu.join("format/umask"), "config:8-15\n").unwrap();
+    fs::write(cpu.join("events/mem-loads"), "event=0xcd,umask=0x1\n").unwrap();
+    let pmus = PmuRegistry::with_sysfs_root(&root).unwrap();
+    (root, pmus)
+}
+
+fn roundtrip(event: &str) -> String {
+    let pc = PerfCounterBuilderLinux::from_event_str(event).expect("Can not parse event");
+    format_event(pc.attributes(), None)
+}
+
+#[test]
+pub fn test_generic_events() {
+    let pc = PerfCounterBuilderLinux::from_event_str("cycles:u").unwrap();
+    let attrs = pc.attributes();
+    assert_eq!(attrs.attr_type(), EventAttrType::Hardware);
+    assert_eq!(attrs.config, 0);
+    assert!(attrs.settings.contains(
+        EventAttrFlags::EVENT_ATTR_EXCLUDE_KERNEL | EventAttrFlags::EVENT_ATTR_EXCLUDE_HV
+    ));
+    assert!(!attrs
+        .settings
+        .contains(EventAttrFlags::EVENT_ATTR_EXCLUDE_USER));
+
+    let pc = PerfCounterBuilderLinux::from_event_str("L1-dcache-load-misses").unwrap();
+    assert_eq!(pc.attributes().attr_type(), EventAttrType::HwCache);
+    assert_eq!(pc.attributes().config, 0x10000);
+
+    let pc = PerfCounterBuilderLinux::from_event_str("r01c2:pp").unwrap();
+    assert_eq!(pc.attributes().attr_type(), EventAttrType::Raw);
+    assert_eq!(pc.attributes().config, 0x1c2);
+    assert!(pc
+        .attributes()
+        .settings
+        .contains(EventAttrFlags::EVENT_ATTR_SAMPLE_IP_REQ_ZERO_SKID));
+
+    let pc = PerfCounterBuilderLinux::from_event_str("mem:0x1000/4:w").unwrap();
+    assert_eq!(pc.attributes().attr_type(), EventAttrType::Breakpoint);
+    assert_eq!(pc.attributes().config1_or_bp_addr, 0x1000);
+
+    assert!(PerfCounterBuilderLinux::from_event_str("cycles:q").is_err());
+    assert!(PerfCounterBuilderLinux::from_event_str("cycles:pppp").is_err());
+}
+
+#[test]
+pub fn test_groups_and_pmus() {
+    let (root, pmus) = fake_pmus();
+
+    let events = parse_events_with_pmus(
+        "cpu/event=0x3c,umask=0x0/k,{cycles,instructions}:u,mem-loads",
+        &pmus,
+    )
+    .expect("Can not parse events");
+    assert_eq!(events.len(), 3);
+
+    match events[0] {
+        ParsedEvent::Event(ref pc) => {
+            assert_eq!(pc.attributes().attr_type, 4);
+            assert_eq!(pc.attributes().config, 0x3c);
+            // The core PMU uses the type of raw events:
+            assert_eq!(format_event(pc.attributes(), Some(&pmus)), "r3c:k");
+        }
+        _ => panic!("Expected a single event"),
+    }
+    match events[1] {
+        ParsedEvent::Group(ref group) => {
+            assert_eq!(group.members().len(), 1);
+            assert_eq!(format_event(group.leader().attributes(), None), "cycles:u");
+            assert_eq!(
+                format_event(group.members()[0].attributes(), None),
+                "instructions:u"
+            );
+        }
+        _ => panic!("Expected a group"),
+    }
+    match events[2] {
+        ParsedEvent::Event(ref pc) => assert_eq!(pc.attributes().config, 0x1cd),
+        _ => panic!("Expected a single event"),
+    }
+
+    let group = PerfCounterGroupBuilder::from_event_str("{cycles,branches,branch-misses}").unwrap();
+    assert_eq!(group.members().len(), 2);
+    assert!(PerfCounterGroupBuilder::from_event_str("cycles").is_err());
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+pub fn test_format_roundtrip() {
+    for event in [
+        "cycles",
+        "instructions:k",
+        "task-clock:u",
+        "LLC-loads",
+        "dTLB-store-misses:uH",
+        "r1a2:ppp",
+        "mem:0x1000/8:rw",
+    ]
+    .iter()
+    {
+        assert_eq!(roundtrip(event), *event);
+    }
+}
@@ -0,0 +1,105 @@
+extern crate perfcnt;
+
+use perfcnt::linux::folded::{FoldedStacks, Weight};
+use perfcnt::linux::maps::Maps;
+use perfcnt::linux::perf_format::{
+    CommRecord, Event, EventData, EventHeader, EventType, MMAPRecord, SampleRecord, ThreadId,
+};
+
+/// PERF_CONTEXT_USER, precedes the user mode part of a callchain.
+const PERF_CONTEXT_USER: u64 = 0xffff_ffff_ffff_fe00;
+
+#[inline(never)]
+fn folded_leaf() -> u64 {
+    1
+}
+
+#[inline(never)]
+fn folded_caller() -> u64 {
+    folded_leaf() + 1
+}
+
+fn event(event_type: EventType, data: EventData) -> Event {
+    Event {
+        header: EventHeader {
+            event_type,
+            misc: 0,
+            size: 0,
+        },
+        data,
+        sample_id: None,
+    }
+}
+
+fn sample(pid: i32, ips: Vec<u64>, period: u64) -> Event {
+    let record = SampleRecord {
+        ptid: Some(ThreadId { pid, tid: pid }),
+        period: Some(period),
+        ips: Some(ips),
+        ..Default::default()
+    };
+    event(EventType::Sample, EventData::Sample(record))
+}
+
+#[test]
+pub fn test_folded_stacks() {
+    assert_eq!(folded_caller(), 2);
+    let pid = std::process::id() as i32;
+    let leaf = folded_leaf as fn() -> u64 as usize as u64;
+    let caller = folded_caller as fn() -> u64 as usize as u64;
+
+    let mut events = vec![event(
+        EventType::Comm,
+        EventData::Comm(CommRecord {
+            ptid: ThreadId { pid, tid: pid },
+            comm: String::from("folded;test"),
+        }),
+    )];
+    for mapping in Maps::from_proc(pid).expect("Can not read maps").iter() {
+        let record = MMAPRecord {
+            pid,
+            tid: pid as u32,
+            addr: mapping.start,
+            len: mapping.end - mapping.start,
+            pgoff: mapping.pgoff,
+            filename: mapping.filename.clone(),
+        };
+        events.push(event(EventType::Mmap, EventData::MMAP(record)));
+    }
+    events.push(sample(
+        pid,
+        vec![PERF_CONTEXT_USER, leaf + 1, caller + 1, 0x10],
+        100,
+    ));
+    events.push(sample(pid, vec![leaf + 2, caller + 1, 0x10], 50));
+    events.push(sample(pid, vec![caller + 1, 0x10], 7));
+    events.push(sample(12345, vec![0x10], 3));
+
+    let mut by_samples = FoldedStacks::new(Weight::Samples);
+    let mut by_period = FoldedStacks::new(Weight::Period);
+    for event in events.iter() {
+        by_samples.process_event(event);
+        by_period.process_event(event);
+    }
+
+    // The comm is the root, the ; in it is replaced:
+    let stacks: Vec<(&String, &u64)> = by_samples.stacks().iter().collect();
+    assert_eq!(stacks.len(), 3);
+    assert_eq!(stacks[0], (&String::from("[unknown];[unknown]"), &1));
+    assert!(stacks[1].0.starts_with("folded:test;[unknown];"));
+    assert!(stacks[1].0.ends_with("folded_caller"));
+    assert_eq!(*stacks[1].1, 1);
+    assert!(stacks[2]
+        .0
+        .ends_with("folded_caller;linux_folded::folded_leaf"));
+    assert_eq!(*stacks[2].1, 2);
+
+    let weights: Vec<u64> = by_period.stacks().values().cloned().collect();
+    assert_eq!(weights, vec![3, 7, 150]);
+
+    let mut out = Vec::new();
+    by_period.write(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out.lines().count(), 3);
+    assert!(out.starts_with("[unknown];[unknown] 3\n"));
+}
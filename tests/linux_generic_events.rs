@@ -1,11 +1,11 @@
 extern crate perfcnt;
 
 use perfcnt::linux::{
-    CacheId, CacheOpId, CacheOpResultId, HardwareEventType, PerfCounterBuilderLinux,
-    SamplingPerfCounter, SoftwareEventType,
+    BreakpointLen, BreakpointType, CacheId, CacheOpId, CacheOpResultId, HardwareEventType,
+    PerfCounterBuilderLinux, PerfCounterGroupBuilder, SamplingPerfCounter,
+    SelfMonitoringPerfCounter, SoftwareEventType,
 };
-use perfcnt::{AbstractPerfCounter, PerfCounter};
-use std::io::Result;
+use perfcnt::{AbstractPerfCounter, PerfCounter, PerfError};
 
 //#[test]
 pub fn sample_event() {
@@ -33,7 +33,7 @@ pub fn sample_event() {
 
 #[test]
 pub fn test_cache_events() {
-    let ret: Result<PerfCounter> = PerfCounterBuilderLinux::from_cache_event(
+    let ret: Result<PerfCounter, PerfError> = PerfCounterBuilderLinux::from_cache_event(
         CacheId::L1D,
         CacheOpId::Read,
         CacheOpResultId::Miss,
@@ -53,7 +53,7 @@ pub fn test_cache_events() {
 
 #[test]
 pub fn test_hardware_counter() {
-    let ret: Result<PerfCounter> =
+    let ret: Result<PerfCounter, PerfError> =
         PerfCounterBuilderLinux::from_hardware_event(HardwareEventType::CacheMisses)
             .exclude_kernel()
             .exclude_idle()
@@ -70,6 +70,58 @@ pub fn test_hardware_counter() {
         Err(e) => assert_eq!(e.raw_os_error().unwrap(), 2),
     }
 }
+#[test]
+pub fn test_counter_group() {
+    let ret = PerfCounterGroupBuilder::new(PerfCounterBuilderLinux::from_software_event(
+        SoftwareEventType::TaskClock,
+    ))
+    .add_member(PerfCounterBuilderLinux::from_software_event(
+        SoftwareEventType::PageFaults,
+    ))
+    .add_member(PerfCounterBuilderLinux::from_software_event(
+        SoftwareEventType::ContextSwitches,
+    ))
+    .finish();
+
+    match ret {
+        Ok(mut group) => {
+            group.reset().expect("Can not reset the group");
+            group.start().expect("Can not start the group");
+            group.stop().expect("Can not stop the group");
+            let res = group.read().expect("Can not read the group");
+            assert_eq!(res.values.len(), 3);
+            assert!(res.values[0].value > 0);
+            assert_ne!(res.values[0].id, res.values[1].id);
+        }
+        Err(e) => assert_eq!(e.raw_os_error().unwrap(), 13),
+    }
+}
+
+#[test]
+pub fn test_self_monitoring_counter() {
+    let ret: Result<PerfCounter, PerfError> =
+        PerfCounterBuilderLinux::from_hardware_event(HardwareEventType::Instructions)
+            .exclude_kernel()
+            .finish();
+
+    match ret {
+        Ok(pc) => {
+            let mut pc = SelfMonitoringPerfCounter::new(pc).expect("Can not map the counter");
+            pc.reset().expect("Can not reset");
+            pc.start().expect("Can not start the counter");
+            let mut sum: u64 = 0;
+            for i in 0..1000 {
+                sum = sum.wrapping_add(i);
+            }
+            let res = pc.read().expect("Can not read the counter");
+            pc.stop().expect("Can not stop the counter");
+            assert!(sum > 0);
+            assert!(res > 0);
+        }
+        Err(e) => assert_eq!(e.raw_os_error().unwrap(), 2),
+    }
+}
+
 /*
 
 #[test]
@@ -102,5 +154,55 @@ pub fn test_software_events() {
 
     // Should be ~= 2
     let res = pc.read_fd().expect("Can not read the counter");
-    assert_eq!(res.value, 2);
+    assert_eq!(res.value(), Some(2));
 }*/
+
+#[test]
+pub fn test_cgroup_counter() {
+    let ret = PerfCounterBuilderLinux::from_software_event(SoftwareEventType::CpuClock)
+        .for_cgroup(".")
+        .finish();
+    assert_eq!(
+        ret.err().map(|e| e.kind()),
+        Some(std::io::ErrorKind::InvalidInput)
+    );
+
+    // The root cgroup contains everything:
+    let ret = PerfCounterBuilderLinux::from_software_event(SoftwareEventType::CpuClock)
+        .for_cgroup(".")
+        .on_cpu(0)
+        .finish();
+    match ret {
+        Ok(pc) => {
+            pc.start().expect("Can not start the counter");
+            pc.stop().expect("Can not stop the counter");
+        }
+        // Counting on a CPU needs CAP_PERFMON (or perf_event_paranoid <= 0):
+        Err(e) => assert_eq!(e.raw_os_error().unwrap(), 13),
+    }
+}
+
+#[test]
+pub fn test_breakpoint_counter() {
+    let mut watched = Box::new(0u64);
+    let addr = &*watched as *const u64 as u64;
+
+    let builder =
+        PerfCounterBuilderLinux::from_breakpoint(addr, BreakpointType::Write, BreakpointLen::Eight);
+    assert_eq!(builder.attributes().config1_or_bp_addr, addr);
+    assert_eq!(builder.attributes().config2_or_bp_len, 8);
+
+    match builder.clone().exclude_kernel().finish() {
+        Ok(mut pc) => {
+            pc.reset().expect("Can not reset");
+            pc.start().expect("Can not start the counter");
+            for i in 0..10 {
+                unsafe { std::ptr::write_volatile(&mut *watched, i) };
+            }
+            pc.stop().expect("Can not stop the counter");
+            assert_eq!(pc.read().expect("Can not read the counter"), 10);
+        }
+        // The kernel or machine has no hardware breakpoints:
+        Err(e) => assert_eq!(e.raw_os_error().unwrap(), 2),
+    }
+}
@@ -0,0 +1,51 @@
+#![cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+
+extern crate perfcnt;
+extern crate x86;
+
+use perfcnt::linux::perf_format::EventAttrFlags;
+use perfcnt::linux::PerfCounterBuilderLinux;
+use x86::perfcnt::intel::events::{COUNTER_MAP, SKYLAKE};
+
+#[test]
+pub fn test_encode_all_intel_events() {
+    for (arch, events) in COUNTER_MAP.entries() {
+        for (name, event) in events.entries() {
+            match PerfCounterBuilderLinux::from_intel_event_description(event) {
+                Ok(_) => assert!(!event.uncore, "{}: {} is an uncore event", arch, name),
+                Err(e) => assert!(event.uncore, "{}: {} failed: {}", arch, name, e),
+            }
+        }
+    }
+}
+
+#[test]
+pub fn test_intel_event_encoding() {
+    let encode = |name: &str| {
+        let event = SKYLAKE.get(name).expect("Event not in table");
+        PerfCounterBuilderLinux::from_intel_event_description(event).expect("Can not encode")
+    };
+
+    // Fixed counter pseudo encodings:
+    assert_eq!(encode("INST_RETIRED.ANY").attributes().config, 0xc0);
+    assert_eq!(encode("CPU_CLK_UNHALTED.THREAD").attributes().config, 0x3c);
+    assert_eq!(
+        encode("CPU_CLK_UNHALTED.REF_TSC").attributes().config,
+        0x0300
+    );
+
+    // Offcore response, the MSR value goes to config1:
+    let event = SKYLAKE
+        .values()
+        .find(|e| e.event_name.starts_with("OFFCORE_RESPONSE."))
+        .expect("No offcore event");
+    let pc = PerfCounterBuilderLinux::from_intel_event_description(event).unwrap();
+    assert_eq!(pc.attributes().config, 0x01b7);
+    assert_eq!(pc.attributes().config1_or_bp_addr, event.msr_value);
+
+    let pc = encode("BR_INST_RETIRED.ALL_BRANCHES_PEBS");
+    assert!(pc
+        .attributes()
+        .settings
+        .contains(EventAttrFlags::EVENT_ATTR_SAMPLE_IP_CONSTANT_SKID));
+}
@@ -0,0 +1,61 @@
+extern crate perfcnt;
+
+use perfcnt::linux::percpu::{online_cpus_in, parse_cpu_list, PerCpuCounter};
+use perfcnt::linux::{PerfCounterBuilderLinux, SoftwareEventType};
+use perfcnt::{AbstractPerfCounter, PerfError};
+use std::fs;
+use std::path::PathBuf;
+
+fn fake_sysfs(name: &str, online: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("perfcnt-{}-{}", name, std::process::id()));
+    let cpu_dir = root.join("devices/system/cpu");
+    fs::create_dir_all(&cpu_dir).expect("Can not create fake sysfs");
+    fs::write(cpu_dir.join("online"), online).expect("Can not write online file");
+    root
+}
+
+#[test]
+pub fn test_parse_cpu_list() {
+    assert_eq!(parse_cpu_list("0\n").unwrap(), vec![0]);
+    assert_eq!(parse_cpu_list("0-3").unwrap(), vec![0, 1, 2, 3]);
+    assert_eq!(parse_cpu_list("0-1,4,6-7\n").unwrap(), vec![0, 1, 4, 6, 7]);
+    assert_eq!(parse_cpu_list("").unwrap(), Vec::<usize>::new());
+    assert!(parse_cpu_list("3-1").is_err());
+    assert!(parse_cpu_list("a-b").is_err());
+}
+
+#[test]
+pub fn test_online_cpus_fake_sysfs() {
+    let root = fake_sysfs("online", "0-2,5\n");
+    assert_eq!(online_cpus_in(&root).unwrap(), vec![0, 1, 2, 5]);
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+pub fn test_percpu_counter() {
+    // Only claim the first CPU is online, so this works on every machine:
+    let root = fake_sysfs("percpu", "0\n");
+    let mut builder = PerfCounterBuilderLinux::from_software_event(SoftwareEventType::TaskClock);
+    builder.exclude_kernel();
+
+    match PerCpuCounter::with_sysfs_root(&builder, &root) {
+        Ok(mut pc) => {
+            assert_eq!(pc.cpus(), vec![0]);
+            pc.reset().expect("Can not reset");
+            pc.start().expect("Can not start the counters");
+            pc.stop().expect("Can not stop the counters");
+            let values = pc.read_per_cpu().expect("Can not read the counters");
+            assert_eq!(values.per_cpu.len(), 1);
+            assert_eq!(values.per_cpu[0].1, values.total);
+        }
+        // Counting on a CPU needs CAP_PERFMON (or perf_event_paranoid <= 0), the
+        // perf_event_open error keeps its diagnostics as the inner error:
+        Err(e) => assert_eq!(
+            e.get_ref()
+                .and_then(|inner| inner.downcast_ref::<PerfError>())
+                .and_then(|inner| inner.raw_os_error()),
+            Some(13)
+        ),
+    }
+    fs::remove_dir_all(&root).unwrap();
+}
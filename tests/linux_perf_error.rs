@@ -0,0 +1,104 @@
+extern crate perfcnt;
+
+use perfcnt::linux::error::{PerfError, PerfPermissions};
+use perfcnt::linux::{HardwareEventType, PerfCounterBuilderLinux, SoftwareEventType};
+use std::io;
+
+const EACCES: i32 = 13;
+
+const UNPRIVILEGED: PerfPermissions = PerfPermissions {
+    paranoid: Some(2),
+    capabilities: Some(0),
+};
+
+fn permission_denied(
+    builder: &PerfCounterBuilderLinux,
+    system_wide: bool,
+    permissions: PerfPermissions,
+) -> String {
+    PerfError::PermissionDenied {
+        attr: Box::new(*builder.attributes()),
+        errno: EACCES,
+        system_wide,
+        permissions,
+    }
+    .to_string()
+}
+
+#[test]
+pub fn test_classify_errno() {
+    let pc = PerfCounterBuilderLinux::from_hardware_event(HardwareEventType::Instructions);
+    let attr = pc.attributes();
+
+    match PerfError::from_errno(2, attr, false) {
+        PerfError::UnsupportedEvent { errno, .. } => assert_eq!(errno, 2),
+        e => panic!("Unexpected error {:?}", e),
+    }
+    match PerfError::from_errno(22, attr, false) {
+        PerfError::InvalidAttributes { attr, .. } => assert_eq!(attr.config, 1),
+        e => panic!("Unexpected error {:?}", e),
+    }
+    match PerfError::from_errno(24, attr, false) {
+        PerfError::TooManyOpenFiles { .. } => (),
+        e => panic!("Unexpected error {:?}", e),
+    }
+    match PerfError::from_errno(EACCES, attr, false) {
+        PerfError::PermissionDenied { .. } => (),
+        e => panic!("Unexpected error {:?}", e),
+    }
+
+    let e = PerfError::from_errno(16, attr, false);
+    assert!(e.attr().is_none());
+    assert_eq!(e.raw_os_error(), Some(16));
+
+    let e = PerfError::from_errno(2, attr, false);
+    assert_eq!(e.kind(), io::ErrorKind::NotFound);
+    assert!(e.to_string().starts_with("Event instructions"));
+    let e: io::Error = e.into();
+    assert_eq!(e.kind(), io::ErrorKind::NotFound);
+    assert!(e.get_ref().unwrap().is::<PerfError>());
+}
+
+#[test]
+pub fn test_permission_hints() {
+    let mut pc = PerfCounterBuilderLinux::from_hardware_event(HardwareEventType::CPUCycles);
+    assert!(permission_denied(&pc, false, UNPRIVILEGED)
+        .contains("counting in kernel mode requires 1 or lower"));
+    assert!(permission_denied(&pc, true, UNPRIVILEGED)
+        .contains("measuring all processes requires 0 or lower"));
+
+    pc.exclude_kernel();
+    assert!(permission_denied(&pc, false, UNPRIVILEGED).contains("seccomp"));
+
+    let locked_down = PerfPermissions {
+        paranoid: Some(4),
+        capabilities: Some(0),
+    };
+    assert!(permission_denied(&pc, false, locked_down).contains("set it to 2"));
+
+    let perfmon = PerfPermissions {
+        paranoid: Some(4),
+        capabilities: Some(1 << 38),
+    };
+    assert!(permission_denied(&pc, false, perfmon).contains("has CAP_PERFMON"));
+}
+
+#[test]
+pub fn test_finish_error() {
+    // A software event that does not exist:
+    let mut attr =
+        *PerfCounterBuilderLinux::from_software_event(SoftwareEventType::CpuClock).attributes();
+    attr.config = 0xffff;
+
+    match PerfCounterBuilderLinux::from_event_attr(&attr)
+        .exclude_kernel()
+        .finish()
+    {
+        Ok(_) => panic!("Opened an invalid event"),
+        Err(e) => {
+            assert_eq!(e.raw_os_error(), Some(2));
+            assert_eq!(e.kind(), std::io::ErrorKind::NotFound);
+            assert_eq!(e.attr().map(|attr| attr.config), Some(0xffff));
+        }
+    }
+}
@@ -0,0 +1,106 @@
+extern crate perfcnt;
+
+use perfcnt::linux::pmu::{ConfigField, FormatField, PmuRegistry};
+use perfcnt::linux::PerfCounterBuilderLinux;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).expect("Can not create directory");
+    fs::write(path, contents).expect("Can not write file");
+}
+
+fn fake_sysfs() -> PathBuf {
+    let root = std::env::temp_dir().join(format!("perfcnt-pmu-{}", std::process::id()));
+    let imc = root.join("bus/event_source/devices/uncore_imc_0");
+    write(&imc.join("type"), "14\n");
+    write(&imc.join("cpumask"), "0,18\n");
+    write(&imc.join("format/event"), "config:0-7\n");
+    write(&imc.join("format/umask"), "config:8-15\n");
+    write(&imc.join("format/edge"), "config:18\n");
+    write(
+        &imc.join("events/cas_count_read"),
+        "event=0x04,umask=0x03\n",
+    );
+    write(&imc.join("events/cas_count_read.unit"), "MiB\n");
+
+    let amd = root.join("bus/event_source/devices/cpu");
+    write(&amd.join("type"), "4\n");
+    write(&amd.join("format/event"), "config:0-7,32-35\n");
+
+    let spe = root.join("bus/event_source/devices/arm_spe_0");
+    write(&spe.join("type"), "9\n");
+    write(&spe.join("format/inv_event_filter"), "config3:0-63\n");
+    // Entries and PMUs that can not be parsed are skipped:
+    write(&spe.join("format/future"), "config9:0-3\n");
+    let broken = root.join("bus/event_source/devices/broken");
+    write(&broken.join("type"), "not a number\n");
+
+    root
+}
+
+#[test]
+pub fn test_format_field() {
+    let f = FormatField::parse("config1:0-7,32-35\n").unwrap();
+    assert_eq!(f.field, ConfigField::Config1);
+    assert_eq!(f.bits, vec![(0, 7), (32, 35)]);
+    assert_eq!(f.width(), 12);
+    assert_eq!(
+        FormatField::parse("config3:0-63").unwrap().field,
+        ConfigField::Config3
+    );
+    assert!(FormatField::parse("config4:0-7").is_err());
+    assert!(FormatField::parse("config:7-0").is_err());
+}
+
+#[test]
+pub fn test_pmu_registry_fake_sysfs() {
+    let root = fake_sysfs();
+    let pmus = PmuRegistry::with_sysfs_root(&root).expect("Can not read fake sysfs");
+    assert_eq!(pmus.pmus().count(), 3);
+    assert_eq!(pmus.skipped().len(), 2);
+
+    let imc = pmus.get("uncore_imc_0").expect("No uncore_imc_0 PMU");
+    assert_eq!(imc.pmu_type, 14);
+    assert_eq!(imc.cpus, Some(vec![0, 18]));
+    assert_eq!(imc.events.len(), 1);
+
+    let attr = pmus
+        .event_attr("uncore_imc_0", "event=0x04,umask=0x03")
+        .unwrap();
+    assert_eq!(attr.attr_type, 14);
+    assert_eq!(attr.config, 0x0304);
+
+    let alias = pmus
+        .event_attr("uncore_imc_0", "cas_count_read,edge")
+        .unwrap();
+    assert_eq!(alias.config, 0x0304 | 1 << 18);
+
+    let split = pmus.event_attr("cpu", "event=0x1d0").unwrap();
+    assert_eq!(split.config, 0xd0 | 0x1 << 32);
+
+    let spe = pmus
+        .event_attr("arm_spe_0", "inv_event_filter=0x8000000000000000")
+        .unwrap();
+    assert_eq!(spe.config3, 1 << 63);
+    assert_eq!(
+        pmus.event_attr("arm_spe_0", "config3=3").unwrap().config3,
+        3
+    );
+    assert_eq!(pmus.get("arm_spe_0").unwrap().skipped.len(), 1);
+
+    let pc = PerfCounterBuilderLinux::from_event_attr(&attr);
+    assert_eq!(pc.attributes().attr_type, 14);
+    assert_eq!(pc.attributes().config, 0x0304);
+
+    let err = |pmu, terms| pmus.event_attr(pmu, terms).err().map(|e| e.kind());
+    assert_eq!(err("uncore_imc_1", "event=1"), Some(ErrorKind::NotFound));
+    assert_eq!(err("uncore_imc_0", "foo=1"), Some(ErrorKind::InvalidInput));
+    assert_eq!(
+        err("uncore_imc_0", "event=0x100"),
+        Some(ErrorKind::InvalidInput)
+    );
+
+    fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,95 @@
+extern crate perfcnt;
+
+use perfcnt::linux::perf_format::EventAttrFlags;
+use perfcnt::linux::probe::SystemProbe;
+use perfcnt::linux::{HardwareEventType, PerfCounterBuilderLinux};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).expect("Can not create directory");
+    fs::write(path, contents).expect("Can not write file");
+}
+
+fn fake_roots(paranoid: &str, cap_eff: &str) -> (PathBuf, PathBuf) {
+    let root = std::env::temp_dir().join(format!("perfcnt-probe-{}", std::process::id()));
+    let proc_root = root.join("proc");
+    let sysfs_root = root.join("sys");
+    write(&proc_root.join("sys/kernel/perf_event_paranoid"), paranoid);
+    write(
+        &proc_root.join("sys/kernel/perf_event_max_sample_rate"),
+        "100000\n",
+    );
+    write(&proc_root.join("sys/kernel/perf_event_mlock_kb"), "516\n");
+    write(
+        &proc_root.join("self/status"),
+        &format!(
+            "Name:\tprobe\nCapInh:\t0000000000000000\nCapEff:\t{}\n",
+            cap_eff
+        ),
+    );
+    write(&sysfs_root.join("bus/event_source/devices/cpu/type"), "4\n");
+    write(
+        &sysfs_root.join("bus/event_source/devices/cpu/rdpmc"),
+        "1\n",
+    );
+    write(
+        &sysfs_root.join("bus/event_source/devices/software/type"),
+        "1\n",
+    );
+    (proc_root, sysfs_root)
+}
+
+#[test]
+pub fn test_read_settings() {
+    let (proc_root, sysfs_root) = fake_roots("2\n", "0000000000000000");
+    let probe = SystemProbe::read_settings(&proc_root, &sysfs_root);
+    assert_eq!(probe.permissions.paranoid, Some(2));
+    assert!(!probe.permissions.is_privileged());
+    assert_eq!(probe.max_sample_rate, Some(100000));
+    assert_eq!(probe.mlock_kb, Some(516));
+    assert_eq!(probe.rdpmc, Some(1));
+    assert!(probe.rdpmc_allowed());
+    assert_eq!(probe.pmus, vec!["cpu", "software"]);
+    assert!(probe.hardware_events.is_empty());
+
+    assert!(!probe.kernel_allowed());
+    assert!(!probe.system_wide_allowed());
+    let mut builder = PerfCounterBuilderLinux::from_hardware_event(HardwareEventType::CPUCycles);
+    probe.restrict(&mut builder);
+    assert!(builder.attributes().settings.contains(
+        EventAttrFlags::EVENT_ATTR_EXCLUDE_KERNEL | EventAttrFlags::EVENT_ATTR_EXCLUDE_HV
+    ));
+
+    // CAP_PERFMON overrides perf_event_paranoid:
+    let (proc_root, sysfs_root) = fake_roots("3\n", "0000004000000000");
+    let probe = SystemProbe::read_settings(&proc_root, &sysfs_root);
+    assert!(probe.kernel_allowed());
+    assert!(probe.system_wide_allowed());
+    let mut builder = PerfCounterBuilderLinux::from_hardware_event(HardwareEventType::CPUCycles);
+    probe.restrict(&mut builder);
+    assert!(!builder
+        .attributes()
+        .settings
+        .contains(EventAttrFlags::EVENT_ATTR_EXCLUDE_KERNEL));
+
+    fs::remove_dir_all(proc_root.parent().unwrap()).unwrap();
+}
+
+#[test]
+pub fn test_probe_events() {
+    let probe = SystemProbe::new();
+    assert_eq!(probe.hardware_events.len(), 10);
+    assert_eq!(probe.hardware_events[0].name, "cycles");
+    assert_eq!(probe.cache_events.len(), 42);
+    assert_eq!(probe.cache_events[1].name, "L1-dcache-load-misses");
+    for event in probe
+        .hardware_events
+        .iter()
+        .chain(probe.cache_events.iter())
+    {
+        if let Some(ref e) = event.error {
+            assert!(e.raw_os_error().is_some());
+        }
+    }
+}
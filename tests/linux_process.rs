@@ -0,0 +1,140 @@
+extern crate perfcnt;
+
+use perfcnt::linux::parser::parse_event;
+use perfcnt::linux::perf_format::{
+    CommRecord, Event, EventAttr, EventAttrFlags, EventData, EventHeader, EventType, ExitRecord,
+    ForkRecord, MMAPRecord, SampleFormatFlags, SampleId, ThreadId, PERF_RECORD_MISC_COMM_EXEC,
+};
+use perfcnt::linux::process::ProcessTracker;
+
+fn event(event_type: EventType, misc: u16, time: u64, data: EventData) -> Event {
+    Event {
+        header: EventHeader {
+            event_type,
+            misc,
+            size: 0,
+        },
+        data,
+        sample_id: Some(SampleId {
+            ptid: None,
+            time: Some(time),
+            id: None,
+            stream_id: None,
+            cpu: None,
+            identifier: None,
+        }),
+    }
+}
+
+fn mmap(time: u64, pid: i32, addr: u64, len: u64, pgoff: u64, filename: &str) -> Event {
+    let record = MMAPRecord {
+        pid,
+        tid: pid as u32,
+        addr,
+        len,
+        pgoff,
+        filename: String::from(filename),
+    };
+    event(EventType::Mmap, 0, time, EventData::MMAP(record))
+}
+
+fn comm(time: u64, pid: i32, name: &str, exec: bool) -> Event {
+    let record = CommRecord {
+        ptid: ThreadId { pid, tid: pid },
+        comm: String::from(name),
+    };
+    let misc = if exec { PERF_RECORD_MISC_COMM_EXEC } else { 0 };
+    event(EventType::Comm, misc, time, EventData::Comm(record))
+}
+
+#[test]
+pub fn test_process_timeline() {
+    let fork = ForkRecord {
+        pid: 200,
+        ppid: 100,
+        tid: 200,
+        ptid: 100,
+        time: 20,
+    };
+    let exit = ExitRecord {
+        pid: 200,
+        ppid: 100,
+        tid: 200,
+        ptid: 100,
+        time: 60,
+    };
+    let events = [
+        comm(5, 100, "a", true),
+        mmap(10, 100, 0x400000, 0x10000, 0, "/bin/a"),
+        mmap(
+            10,
+            -1,
+            0xffffffff81000000,
+            0x1000000,
+            0,
+            "[kernel.kallsyms]",
+        ),
+        event(EventType::Fork, 0, 20, EventData::Fork(fork)),
+        mmap(30, 200, 0x408000, 0x1000, 0, "/lib/x.so"),
+        comm(40, 200, "b", true),
+        mmap(50, 200, 0x400000, 0x10000, 0, "/bin/b"),
+        event(EventType::Exit, 0, 60, EventData::Exit(exit)),
+    ];
+    let mut tracker = ProcessTracker::new();
+    for event in events.iter() {
+        tracker.process_event(event);
+    }
+    let file = |pid, time, addr| tracker.lookup(pid, time, addr).map(|m| m.filename.as_str());
+
+    assert_eq!(file(100, 25, 0x408000), Some("/bin/a"));
+    assert_eq!(file(100, 25, 0xffffffff81000010), Some("[kernel.kallsyms]"));
+    assert_eq!(file(200, 15, 0x408000), None);
+    assert_eq!(file(200, 25, 0x408000), Some("/bin/a"));
+    assert_eq!(file(200, 35, 0x408000), Some("/lib/x.so"));
+    assert_eq!(file(200, 35, 0x409000), Some("/bin/a"));
+    assert_eq!(tracker.lookup(200, 35, 0x409000).unwrap().pgoff, 0x9000);
+    assert_eq!(tracker.maps_at(200, 35).len(), 3);
+    assert_eq!(file(200, 45, 0x400000), None);
+    assert_eq!(file(200, 55, 0x408000), Some("/bin/b"));
+    assert_eq!(file(200, 65, 0x408000), None);
+
+    assert_eq!(tracker.comm(200, 25), Some("a"));
+    assert_eq!(tracker.comm(200, 45), Some("b"));
+    assert_eq!(tracker.parent(200), Some(100));
+    assert_eq!(tracker.get(200).unwrap().exit_time, Some(60));
+    assert_eq!(tracker.get(100).unwrap().exit_time, None);
+}
+
+#[test]
+pub fn test_parse_sample_id_all() {
+    let attrs = vec![EventAttr {
+        sample_type: SampleFormatFlags::PERF_SAMPLE_TID | SampleFormatFlags::PERF_SAMPLE_TIME,
+        settings: EventAttrFlags::EVENT_ATTR_SAMPLE_ID_ALL,
+        ..Default::default()
+    }];
+
+    let mut record = Vec::new();
+    record.extend_from_slice(&1u32.to_le_bytes()); // PERF_RECORD_MMAP
+    record.extend_from_slice(&0u16.to_le_bytes());
+    record.extend_from_slice(&72u16.to_le_bytes());
+    record.extend_from_slice(&42i32.to_le_bytes());
+    record.extend_from_slice(&43u32.to_le_bytes());
+    record.extend_from_slice(&0x400000u64.to_le_bytes());
+    record.extend_from_slice(&0x1000u64.to_le_bytes());
+    record.extend_from_slice(&0u64.to_le_bytes());
+    record.extend_from_slice(b"/bin/true\0\0\0\0\0\0\0");
+    record.extend_from_slice(&42i32.to_le_bytes());
+    record.extend_from_slice(&43i32.to_le_bytes());
+    record.extend_from_slice(&1234u64.to_le_bytes());
+    assert_eq!(record.len(), 72);
+
+    let (_, event) = parse_event(&record, &attrs).expect("Can not parse event");
+    match event.data {
+        EventData::MMAP(ref mmap) => assert_eq!(mmap.filename, "/bin/true"),
+        _ => panic!("Not an MMAP record"),
+    }
+    assert_eq!(event.time(), Some(1234));
+    let sample_id = event.sample_id.expect("No sample_id");
+    assert_eq!(sample_id.ptid.unwrap().tid, 43);
+    assert!(sample_id.cpu.is_none());
+}
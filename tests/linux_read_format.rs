@@ -0,0 +1,129 @@
+extern crate perfcnt;
+
+use perfcnt::linux::parser::parse_read_format;
+use perfcnt::linux::perf_format::{ReadFormatFlags, ReadValue, ScaledValue};
+use perfcnt::linux::{PerfCounterBuilderLinux, SoftwareEventType};
+use perfcnt::AbstractPerfCounter;
+
+fn to_bytes(values: &[u64]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .collect()
+}
+
+#[test]
+pub fn test_read_format_single() {
+    let flags = ReadFormatFlags::FORMAT_TOTAL_TIME_RUNNING
+        | ReadFormatFlags::FORMAT_ID
+        | ReadFormatFlags::FORMAT_LOST;
+    let bytes = to_bytes(&[42, 1000, 7, 3]);
+
+    let (rest, rf) = parse_read_format(&bytes, flags).expect("Can not parse read format");
+    assert!(rest.is_empty());
+    assert_eq!(rf.time_enabled, None);
+    assert_eq!(rf.time_running, Some(1000));
+    assert_eq!(
+        rf.values,
+        vec![ReadValue {
+            value: 42,
+            id: Some(7),
+            lost: Some(3)
+        }]
+    );
+}
+
+#[test]
+pub fn test_read_format_group() {
+    let flags = ReadFormatFlags::FORMAT_GROUP
+        | ReadFormatFlags::FORMAT_TOTAL_TIME_ENABLED
+        | ReadFormatFlags::FORMAT_ID
+        | ReadFormatFlags::FORMAT_LOST;
+    let bytes = to_bytes(&[2, 500, 10, 1, 0, 20, 2, 5]);
+
+    let (rest, rf) = parse_read_format(&bytes, flags).expect("Can not parse read format");
+    assert!(rest.is_empty());
+    assert_eq!(rf.time_enabled, Some(500));
+    assert_eq!(rf.time_running, None);
+    assert_eq!(rf.value(), Some(10));
+    assert_eq!(rf.values.len(), 2);
+    assert_eq!(rf.values[1].id, Some(2));
+    assert_eq!(rf.values[1].lost, Some(5));
+}
+
+#[test]
+pub fn test_scaled_values() {
+    let full = ScaledValue::new(100, 50, 50);
+    assert_eq!(full.scaled, Some(100));
+    assert!(!full.is_multiplexed());
+
+    let half = ScaledValue::new(100, 50, 25);
+    assert_eq!(half.scaled, Some(200));
+    assert!(half.is_multiplexed());
+    assert_eq!(half.running_ratio(), 0.5);
+
+    let never = ScaledValue::new(0, 50, 0);
+    assert!(never.never_scheduled());
+    assert_eq!(never.scaled, None);
+    assert_eq!(never.running_ratio(), 0.0);
+    assert!(!never.never_enabled());
+
+    // Not even enabled, that's no zero count either:
+    let disabled = ScaledValue::new(0, 0, 0);
+    assert!(disabled.never_enabled());
+    assert!(disabled.never_scheduled());
+    assert_eq!(disabled.scaled, None);
+    assert_eq!(disabled.running_ratio(), 0.0);
+    assert!(!disabled.is_multiplexed());
+}
+
+#[test]
+pub fn test_read_format_scaled() {
+    let flags =
+        ReadFormatFlags::FORMAT_TOTAL_TIME_ENABLED | ReadFormatFlags::FORMAT_TOTAL_TIME_RUNNING;
+    let bytes = to_bytes(&[30, 300, 100]);
+
+    let (_, rf) = parse_read_format(&bytes, flags).expect("Can not parse read format");
+    let scaled = rf.scaled_values().expect("Timing information missing");
+    assert_eq!(scaled[0].scaled, Some(90));
+    assert_eq!(scaled[0].raw, 30);
+}
+
+#[test]
+pub fn test_scaling_is_automatic() {
+    let mut builder = PerfCounterBuilderLinux::from_software_event(SoftwareEventType::TaskClock);
+    assert!(builder.attributes().read_format.is_empty());
+    builder.enable_read_format_scaling();
+    assert_eq!(
+        builder.attributes().read_format,
+        ReadFormatFlags::FORMAT_TOTAL_TIME_ENABLED | ReadFormatFlags::FORMAT_TOTAL_TIME_RUNNING
+    );
+
+    // A default counter can be scaled, and read still returns just the value:
+    let ret = PerfCounterBuilderLinux::from_software_event(SoftwareEventType::TaskClock)
+        .exclude_kernel()
+        .disable()
+        .finish();
+    match ret {
+        Ok(mut pc) => {
+            assert!(pc.attributes().read_format.contains(
+                ReadFormatFlags::FORMAT_TOTAL_TIME_ENABLED
+                    | ReadFormatFlags::FORMAT_TOTAL_TIME_RUNNING
+            ));
+            let never = pc.read_scaled().expect("Can not read the counter");
+            assert!(never.never_enabled());
+            assert_eq!(never.scaled, None);
+
+            pc.start().expect("Can not start the counter");
+            let sum: u64 = (0..100_000u64).sum();
+            pc.stop().expect("Can not stop the counter");
+            assert!(sum > 0);
+            let value = pc.read_scaled().expect("Can not read the counter");
+            assert!(value.time_enabled > 0);
+            assert_eq!(value.scaled, Some(value.raw));
+            assert_eq!(pc.read().expect("Can not read the counter"), value.raw);
+        }
+        // Only if perf_event_paranoid does not allow any events:
+        Err(e) => assert_eq!(e.raw_os_error().unwrap(), 13),
+    }
+}
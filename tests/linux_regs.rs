@@ -0,0 +1,83 @@
+extern crate perfcnt;
+
+use perfcnt::linux::parser::parse_sample_record;
+use perfcnt::linux::perf_format::{EventAttr, SampleFormatFlags};
+use perfcnt::linux::regs::{regs_mask, Aarch64Reg, RegArch, Register, X86Reg};
+use perfcnt::linux::{HardwareEventType, PerfCounterBuilderLinux};
+
+fn to_bytes(values: &[u64]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .collect()
+}
+
+#[test]
+pub fn test_register_masks() {
+    assert_eq!(regs_mask(&[X86Reg::Ip, X86Reg::Sp, X86Reg::Bp]), 0x1c0);
+    assert_eq!(regs_mask(&[Aarch64Reg::Pc, Aarch64Reg::Sp]), 0x1_8000_0000);
+    assert_eq!(X86Reg::R15.name(), "R15");
+    assert_eq!(Aarch64Reg::Lr.name(), "lr");
+
+    let mut pc = PerfCounterBuilderLinux::from_hardware_event(HardwareEventType::CPUCycles);
+    pc.sample_user_regs(&[X86Reg::Ip, X86Reg::Sp]);
+    assert!(pc.attributes().sample_type.has_regs_user());
+    assert_eq!(pc.attributes().sample_regs_user, 0x180);
+    pc.sample_intr_regs(&[X86Reg::Ip]);
+    assert!(pc.attributes().sample_type.has_regs_intr());
+    assert_eq!(pc.attributes().sample_regs_intr, 0x100);
+}
+
+#[test]
+pub fn test_register_arch() {
+    assert_eq!(RegArch::from_machine("x86_64"), Some(RegArch::X86_64));
+    assert_eq!(RegArch::from_machine("i686"), Some(RegArch::X86));
+    assert_eq!(RegArch::from_machine("aarch64"), Some(RegArch::Aarch64));
+    assert_eq!(RegArch::from_machine("riscv64"), None);
+
+    assert_eq!(RegArch::X86_64.for_abi(2), Some(RegArch::X86_64));
+    assert_eq!(RegArch::X86_64.for_abi(1), Some(RegArch::X86));
+    assert_eq!(RegArch::X86_64.for_abi(0), None);
+    assert_eq!(RegArch::Aarch64.for_abi(1), None);
+
+    assert_eq!(RegArch::X86.register_name(X86Reg::R8.index()), None);
+    assert_eq!(RegArch::X86_64.all_registers().count_ones(), 24);
+    assert_eq!(RegArch::Aarch64.all_registers().count_ones(), 33);
+}
+
+#[test]
+pub fn test_parse_user_regs() {
+    let attr = EventAttr {
+        sample_type: SampleFormatFlags::PERF_SAMPLE_IP | SampleFormatFlags::PERF_SAMPLE_REGS_USER,
+        sample_regs_user: regs_mask(&[X86Reg::Bp, X86Reg::Sp, X86Reg::Ip, X86Reg::R8]),
+        ..Default::default()
+    };
+
+    // 64-bit task:
+    let sample = to_bytes(&[0x401000, 2, 0x7ff0, 0x7fe0, 0x401000, 8]);
+    let (rest, record) = parse_sample_record(&sample, &attr).expect("Can not parse sample");
+    assert!(rest.is_empty());
+    let regs = record
+        .user_regs(&attr, RegArch::X86_64)
+        .expect("No registers");
+    assert_eq!(regs["BP"], 0x7ff0);
+    assert_eq!(regs["SP"], 0x7fe0);
+    assert_eq!(regs["IP"], 0x401000);
+    assert_eq!(regs["R8"], 8);
+
+    // 32-bit task, R8 is not a register there:
+    let sample = to_bytes(&[0x8048000, 1, 0xbff0, 0xbfe0, 0x8048000, 0]);
+    let (_, record) = parse_sample_record(&sample, &attr).expect("Can not parse sample");
+    let regs = record
+        .user_regs(&attr, RegArch::X86_64)
+        .expect("No registers");
+    assert_eq!(regs.len(), 3);
+    assert_eq!(regs["IP"], 0x8048000);
+
+    // Kernel thread, no registers follow the ABI:
+    let sample = to_bytes(&[0xffffffff81000000, 0]);
+    let (rest, record) = parse_sample_record(&sample, &attr).expect("Can not parse sample");
+    assert!(rest.is_empty());
+    assert!(record.regs_user.is_none());
+    assert!(record.user_regs(&attr, RegArch::X86_64).is_none());
+}
@@ -0,0 +1,68 @@
+extern crate perfcnt;
+
+use perfcnt::linux::maps::{Mapping, Maps};
+use perfcnt::linux::symbolize::Symbolizer;
+
+/// PERF_CONTEXT_USER, precedes the user mode part of a callchain.
+const PERF_CONTEXT_USER: u64 = 0xffff_ffff_ffff_fe00;
+
+#[inline(never)]
+fn symbolize_me() -> u64 {
+    42
+}
+
+#[test]
+pub fn test_resolve_own_function() {
+    assert_eq!(symbolize_me(), 42);
+    let pid = std::process::id() as i32;
+    let address = symbolize_me as fn() -> u64 as usize as u64;
+
+    let mut symbolizer = Symbolizer::new();
+    let maps = Maps::from_proc(pid).expect("Can not read maps");
+    for mapping in maps.iter() {
+        symbolizer.add_mapping(pid, mapping.clone());
+    }
+
+    let resolved = symbolizer
+        .resolve(pid, address + 1)
+        .expect("Address is not mapped");
+    assert_eq!(
+        resolved.binary,
+        std::env::current_exe().unwrap().to_str().unwrap()
+    );
+    assert!(resolved
+        .symbol
+        .as_ref()
+        .expect("No symbol")
+        .ends_with("symbolize_me"));
+    assert_eq!(resolved.symbol_offset, 1);
+    assert!(resolved.to_string().contains("symbolize_me+0x1 ("));
+
+    let frames = symbolizer.symbolize_callchain(pid, &[PERF_CONTEXT_USER, address, 0x10]);
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0].symbol_offset, 0);
+    assert_eq!(frames[1].binary, "");
+    assert_eq!(frames[1].to_string(), "+0x10");
+}
+
+#[test]
+pub fn test_resolve_kernel_and_unreadable() {
+    let mut symbolizer = Symbolizer::new();
+    symbolizer.add_mapping(
+        -1,
+        Mapping::new(0xffffffff81000000, 0x1000000, 0, "[kernel.kallsyms]"),
+    );
+    symbolizer.add_mapping(7, Mapping::new(0x400000, 0x2000, 0x1000, "/not/an/elf"));
+    symbolizer.add_object("/not/an/elf", &[0; 64]);
+
+    let kernel = symbolizer
+        .resolve(7, 0xffffffff81000100)
+        .expect("Kernel is not mapped");
+    assert_eq!(kernel.binary, "[kernel.kallsyms]");
+    assert_eq!(kernel.symbol, None);
+
+    let user = symbolizer.resolve(7, 0x400010).expect("Not mapped");
+    assert_eq!(user.offset, 0x1010);
+    assert_eq!(user.to_string(), "/not/an/elf+0x1010");
+    assert!(symbolizer.resolve(8, 0x400010).is_none());
+}
@@ -0,0 +1,32 @@
+extern crate perfcnt;
+
+use perfcnt::linux::perf_format::EventAttrType;
+use perfcnt::linux::PerfCounterBuilderLinux;
+use std::fs;
+use std::io::ErrorKind;
+
+#[test]
+pub fn test_tracepoint_fake_tracefs() {
+    let root = std::env::temp_dir().join(format!("perfcnt-tracefs-{}", std::process::id()));
+    let event_dir = root.join("events/sched/sched_switch");
+    fs::create_dir_all(&event_dir).expect("Can not create fake tracefs");
+    fs::write(event_dir.join("id"), "316\n").expect("Can not write id file");
+
+    let pc = PerfCounterBuilderLinux::from_tracepoint_in("sched:sched_switch", &root)
+        .expect("Can not find tracepoint");
+    assert_eq!(pc.attributes().attr_type(), EventAttrType::TracePoint);
+    assert_eq!(pc.attributes().config, 316);
+
+    let err = PerfCounterBuilderLinux::from_tracepoint_in("sched:does_not_exist", &root)
+        .err()
+        .expect("Found tracepoint that does not exist");
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(format!("{}", err).contains("sched:does_not_exist"));
+
+    let err = PerfCounterBuilderLinux::from_tracepoint_in("sched_switch", &root)
+        .err()
+        .expect("Accepted tracepoint without subsystem");
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+    fs::remove_dir_all(&root).unwrap();
+}
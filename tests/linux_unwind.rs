@@ -0,0 +1,91 @@
+extern crate perfcnt;
+
+use std::collections::BTreeMap;
+
+use perfcnt::linux::maps::{Mapping, Maps};
+use perfcnt::linux::regs::RegArch;
+use perfcnt::linux::unwind::Unwinder;
+
+#[inline(never)]
+fn callee() -> u64 {
+    1
+}
+
+#[inline(never)]
+fn caller() -> u64 {
+    callee() + 1
+}
+
+#[test]
+pub fn test_maps() {
+    let mut maps = Maps::parse_proc_maps(
+        "00400000-00452000 r-xp 00000000 08:02 173521      /usr/bin/dbus-daemon\n\
+         00651000-00652000 rw-p 00051000 08:02 173521      /usr/bin/dbus-daemon\n\
+         7fff8d5fe000-7fff8d600000 r-xp 00000000 00:00 0   [vdso]\n\
+         invalid line\n",
+    );
+    assert_eq!(maps.len(), 3);
+    assert_eq!(
+        maps.find(0x400100).unwrap().filename,
+        "/usr/bin/dbus-daemon"
+    );
+    assert_eq!(maps.find(0x651010).unwrap().file_offset(0x651010), 0x51010);
+    assert!(!maps.find(0x7fff8d5fe000).unwrap().is_file());
+    assert!(maps.find(0x452000).is_none());
+
+    // A new mapping in the middle splits the old one:
+    maps.insert(Mapping::new(0x410000, 0x1000, 0, "/lib/libc.so.6"));
+    assert_eq!(maps.len(), 5);
+    assert_eq!(maps.find(0x410800).unwrap().filename, "/lib/libc.so.6");
+    let tail = maps.find(0x411000).unwrap();
+    assert_eq!(tail.filename, "/usr/bin/dbus-daemon");
+    assert_eq!(tail.pgoff, 0x11000);
+    assert_eq!(maps.find(0x40ffff).unwrap().end, 0x410000);
+}
+
+#[test]
+pub fn test_unwind_without_unwind_info() {
+    let mut maps = Maps::new();
+    maps.insert(Mapping::new(0x400000, 0x1000, 0, "/not/an/elf"));
+    let mut unwinder = Unwinder::new();
+    unwinder.add_object("/not/an/elf", vec![0; 64]);
+
+    let mut regs = BTreeMap::new();
+    regs.insert("IP", 0x400010);
+    regs.insert("SP", 0x7ff000);
+    let callchain = unwinder.unwind(RegArch::X86_64, &regs, &[0; 16], &maps);
+    assert_eq!(callchain, vec![0x400010]);
+
+    regs.remove("IP");
+    assert!(unwinder
+        .unwind(RegArch::X86_64, &regs, &[0; 16], &maps)
+        .is_empty());
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+pub fn test_unwind_x86_64() {
+    assert_eq!(caller(), 2);
+    let maps = Maps::from_proc(std::process::id() as i32).expect("Can not read maps");
+    let callee = callee as fn() -> u64 as usize as u64;
+    let caller = caller as fn() -> u64 as usize as u64;
+
+    // At the first instruction of `callee` the return address is on top of the stack,
+    // pretend it returns into `caller`, whose return address is 0 (the end):
+    let sp = 0x7ffd_0000_1000u64;
+    let mut stack = Vec::new();
+    stack.extend_from_slice(&(caller + 1).to_le_bytes());
+    stack.extend_from_slice(&0u64.to_le_bytes());
+    let mut regs = BTreeMap::new();
+    regs.insert("IP", callee);
+    regs.insert("SP", sp);
+    regs.insert("BP", 0);
+
+    let mut unwinder = Unwinder::new();
+    let callchain = unwinder.unwind(RegArch::X86_64, &regs, &stack, &maps);
+    assert_eq!(callchain, vec![callee, caller + 1]);
+
+    unwinder.set_max_frames(1);
+    let callchain = unwinder.unwind(RegArch::X86_64, &regs, &stack, &maps);
+    assert_eq!(callchain, vec![callee]);
+}
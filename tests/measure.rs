@@ -0,0 +1,98 @@
+extern crate perfcnt;
+
+use perfcnt::linux::{PerfCounterBuilderLinux, SoftwareEventType};
+use perfcnt::{AbstractPerfCounter, Measure, PerfCounter};
+use std::cell::Cell;
+use std::io;
+
+fn software_counter(event: SoftwareEventType) -> Option<PerfCounter> {
+    match PerfCounterBuilderLinux::from_software_event(event)
+        .exclude_kernel()
+        .finish()
+    {
+        Ok(pc) => Some(pc),
+        // Only if perf_event_paranoid does not allow any events:
+        Err(e) => {
+            assert_eq!(e.raw_os_error().unwrap(), 13);
+            None
+        }
+    }
+}
+
+/// Counts how often it was started, so every machine can run the tests.
+#[derive(Default)]
+struct FakeCounter {
+    running: Cell<bool>,
+    starts: Cell<u64>,
+}
+
+impl AbstractPerfCounter for FakeCounter {
+    fn reset(&self) -> Result<(), io::Error> {
+        self.starts.set(0);
+        Ok(())
+    }
+
+    fn start(&self) -> Result<(), io::Error> {
+        self.running.set(true);
+        self.starts.set(self.starts.get() + 1);
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), io::Error> {
+        self.running.set(false);
+        Ok(())
+    }
+
+    fn read(&mut self) -> Result<u64, io::Error> {
+        Ok(self.starts.get())
+    }
+}
+
+#[test]
+pub fn test_measure_fake_counter() {
+    let mut fake = FakeCounter::default();
+    let (running, starts) = fake.measure(|| true).expect("Can not measure");
+    assert!(running);
+    assert_eq!(starts, 1);
+    assert!(!fake.running.get());
+
+    let mut fakes = [FakeCounter::default(), FakeCounter::default()];
+    let mut result = None;
+    {
+        let _guard = fakes.guard(&mut result).expect("Can not start");
+    }
+    assert!(fakes.iter().all(|f| !f.running.get()));
+    assert_eq!(result, Some(vec![1, 1]));
+}
+
+#[test]
+pub fn test_measure_closure() {
+    if let Some(mut pc) = software_counter(SoftwareEventType::TaskClock) {
+        let (sum, _clock) = pc
+            .measure(|| (0..1000u64).sum::<u64>())
+            .expect("Can not measure");
+        assert_eq!(sum, 499500);
+    }
+}
+
+#[test]
+pub fn test_measure_guard() {
+    let counters: Option<Vec<PerfCounter>> =
+        vec![SoftwareEventType::TaskClock, SoftwareEventType::CpuClock]
+            .into_iter()
+            .map(software_counter)
+            .collect();
+
+    if let Some(mut counters) = counters {
+        let mut result = None;
+        {
+            let _guard = counters.guard(&mut result).expect("Can not start");
+        }
+        assert_eq!(result.map(|v| v.len()), Some(2));
+
+        let mut result = None;
+        let guard = counters.guard(&mut result).expect("Can not start");
+        guard.finish().expect("Can not stop");
+        assert!(result.is_some());
+    }
+}